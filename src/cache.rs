@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::http::{HttpClient, HttpRequest, HttpResponse};
+
+/// How a cached request was answered, recorded in the execution history.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CacheStatus {
+    /// Served from the cache without a request.
+    Hit,
+    /// The server answered 304 Not Modified to a conditional request.
+    Revalidated,
+    Miss,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "hit",
+            CacheStatus::Revalidated => "revalidated",
+            CacheStatus::Miss => "miss",
+        }
+    }
+}
+
+impl fmt::Display for CacheStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedResponse {
+    /// Digest of the request, see [`cache_key`].
+    pub key: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// Unix timestamp in seconds.
+    pub stored_at: i64,
+    /// Seconds the response is fresh for, `None` always revalidates.
+    pub max_age: Option<u64>,
+}
+
+impl CachedResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    fn is_fresh(&self, now: i64) -> bool {
+        match self.max_age {
+            None => false,
+            Some(max_age) => now - self.stored_at < max_age as i64,
+        }
+    }
+}
+
+/// Where cached responses are kept between requests.
+pub trait CacheStore: fmt::Debug {
+    fn get(&self, key: &str) -> Result<Option<CachedResponse>, Box<dyn Error>>;
+    fn put(&self, response: &CachedResponse) -> Result<(), Box<dyn Error>>;
+}
+
+/// Cache for the lifetime of the process.
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    responses: Mutex<HashMap<String, CachedResponse>>,
+}
+
+impl CacheStore for MemoryCache {
+    fn get(&self, key: &str) -> Result<Option<CachedResponse>, Box<dyn Error>> {
+        Ok(self.responses.lock().unwrap().get(key).cloned())
+    }
+
+    fn put(&self, response: &CachedResponse) -> Result<(), Box<dyn Error>> {
+        self.responses.lock().unwrap().insert(response.key.clone(), response.clone());
+        Ok(())
+    }
+}
+
+/// One JSON file per response, so the cache outlives the run.
+#[derive(Debug)]
+pub struct DirCache {
+    dir: PathBuf,
+}
+
+impl DirCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DirCache {
+            dir: dir.into(),
+        }
+    }
+
+    /// Keys are hex digests, so they make valid file names.
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+impl CacheStore for DirCache {
+    fn get(&self, key: &str) -> Result<Option<CachedResponse>, Box<dyn Error>> {
+        let path = self.path(key);
+        match path.exists() {
+            false => Ok(None),
+            true => {
+                let response: CachedResponse = serde_json::from_str(&fs::read_to_string(path)?)?;
+                match response.key == key {
+                    true => Ok(Some(response)),
+                    false => Ok(None),
+                }
+            }
+        }
+    }
+
+    fn put(&self, response: &CachedResponse) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(&response.key);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(response)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Answers the GET requests of steps with a `cache` option from a [`CacheStore`], honouring
+/// `Cache-Control`, `ETag` and `Last-Modified`. Other requests go straight to the inner client.
+#[derive(Debug)]
+pub struct CachingClient {
+    inner: Box<dyn HttpClient>,
+    store: Box<dyn CacheStore>,
+}
+
+impl CachingClient {
+    pub fn new(inner: Box<dyn HttpClient>, store: Box<dyn CacheStore>) -> Self {
+        CachingClient {
+            inner,
+            store,
+        }
+    }
+}
+
+impl HttpClient for CachingClient {
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Box<dyn Error>> {
+        let cache = match (&request.cache, request.method.as_str()) {
+            (Some(cache), "GET") => cache,
+            _ => return self.inner.send(request),
+        };
+
+        let key = cache_key(request);
+        let now = Utc::now().timestamp();
+        let cached = self.store.get(&key)?;
+        if let Some(cached) = &cached {
+            if cached.is_fresh(now) {
+                return Ok(response(cached, CacheStatus::Hit));
+            }
+        }
+
+        let mut conditional = request.clone();
+        if let Some(cached) = &cached {
+            if let Some(etag) = cached.header("etag") {
+                conditional.headers.push(("If-None-Match".to_owned(), etag.to_owned()));
+            }
+            if let Some(last_modified) = cached.header("last-modified") {
+                conditional.headers.push(("If-Modified-Since".to_owned(), last_modified.to_owned()));
+            }
+        }
+
+        let mut fetched = self.inner.send(&conditional)?;
+        if fetched.status == 304 && cached.is_none() {
+            // Nothing to reuse, e.g. the cache directory was cleared: the step's own conditional headers are dropped.
+            let mut unconditional = request.clone();
+            unconditional.headers.retain(|(name, _)| !is_conditional(name));
+            fetched = self.inner.send(&unconditional)?;
+            if fetched.status == 304 {
+                return Err(format!("{} {} answered 304 Not Modified but no response is cached", request.method, request.url).into());
+            }
+        }
+        let directives = CacheControl::parse(fetched.header("cache-control"));
+        let max_age = match directives.no_cache {
+            true => None,
+            false => directives.max_age.or(cache.ttl_seconds),
+        };
+
+        match (fetched.status, cached) {
+            (304, Some(mut cached)) => {
+                cached.stored_at = now;
+                cached.max_age = max_age;
+                self.store.put(&cached)?;
+                Ok(response(&cached, CacheStatus::Revalidated))
+            }
+            (status, _) => {
+                let validated = fetched.header("etag").is_some() || fetched.header("last-modified").is_some();
+                if status == 200 && !directives.no_store && (validated || max_age.is_some()) {
+                    self.store.put(&CachedResponse {
+                        key,
+                        status,
+                        headers: fetched.headers.clone(),
+                        body: fetched.body.clone(),
+                        stored_at: now,
+                        max_age,
+                    })?;
+                }
+                fetched.cache = Some(CacheStatus::Miss);
+                Ok(fetched)
+            }
+        }
+    }
+}
+
+/// SHA-256 of the request. The headers are part of it, so responses for different credentials are kept apart,
+/// and only the digest is stored, so header and query values taken from secrets never reach the disk.
+fn cache_key(request: &HttpRequest) -> String {
+    let mut query = request.query.clone();
+    query.sort();
+    let mut headers: Vec<(String, String)> = request.headers.iter().map(|(name, value)| (name.to_lowercase(), value.clone())).collect();
+    headers.sort();
+    hex::encode(Sha256::digest(format!("{} {} {:?} {:?}", request.method, request.url, query, headers)))
+}
+
+fn is_conditional(header: &str) -> bool {
+    ["if-none-match", "if-modified-since", "if-match", "if-unmodified-since"].iter().any(|name| header.eq_ignore_ascii_case(name))
+}
+
+fn response(cached: &CachedResponse, status: CacheStatus) -> HttpResponse {
+    HttpResponse {
+        status: cached.status,
+        headers: cached.headers.clone(),
+        body: cached.body.clone(),
+        cache: Some(status),
+    }
+}
+
+#[derive(Debug, Default)]
+struct CacheControl {
+    max_age: Option<u64>,
+    no_cache: bool,
+    no_store: bool,
+}
+
+impl CacheControl {
+    fn parse(header: Option<&str>) -> Self {
+        let mut directives = CacheControl::default();
+        for directive in header.unwrap_or_default().split(',') {
+            let directive = directive.trim().to_lowercase();
+            match directive.split_once('=') {
+                Some(("max-age", seconds)) => directives.max_age = seconds.trim_matches('"').parse().ok(),
+                _ if directive == "no-cache" => directives.no_cache = true,
+                _ if directive == "no-store" => directives.no_store = true,
+                _ => {}
+            }
+        }
+        directives
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::models::HttpCache;
+
+    /// Answers with the queued responses in order and keeps the requests it was sent.
+    #[derive(Debug, Clone, Default)]
+    struct FakeServer(Arc<Mutex<(VecDeque<HttpResponse>, Vec<HttpRequest>)>>);
+
+    impl FakeServer {
+        fn respond(&self, status: u16, headers: &[(&str, &str)], body: &str) {
+            self.0.lock().unwrap().0.push_back(HttpResponse {
+                status,
+                headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+                body: body.to_owned(),
+                cache: None,
+            });
+        }
+
+        fn sent(&self) -> Vec<HttpRequest> {
+            self.0.lock().unwrap().1.clone()
+        }
+    }
+
+    impl HttpClient for FakeServer {
+        fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Box<dyn Error>> {
+            let mut server = self.0.lock().unwrap();
+            server.1.push(request.clone());
+            server.0.pop_front().ok_or_else(|| format!("unexpected request {}", request.url).into())
+        }
+    }
+
+    /// Memory cache the test can still reach after handing it to the client.
+    #[derive(Debug, Clone, Default)]
+    struct SharedCache(Arc<MemoryCache>);
+
+    impl SharedCache {
+        /// Moves every cached response `seconds` into the past.
+        fn age(&self, seconds: i64) {
+            for response in self.0.responses.lock().unwrap().values_mut() {
+                response.stored_at -= seconds;
+            }
+        }
+    }
+
+    impl CacheStore for SharedCache {
+        fn get(&self, key: &str) -> Result<Option<CachedResponse>, Box<dyn Error>> {
+            self.0.get(key)
+        }
+
+        fn put(&self, response: &CachedResponse) -> Result<(), Box<dyn Error>> {
+            self.0.put(response)
+        }
+    }
+
+    fn client() -> (CachingClient, FakeServer, SharedCache) {
+        let (server, store) = (FakeServer::default(), SharedCache::default());
+        (CachingClient::new(Box::new(server.clone()), Box::new(store.clone())), server, store)
+    }
+
+    fn get(ttl_seconds: Option<u64>) -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_owned(),
+            url: "https://example.com/items".to_owned(),
+            query: vec![],
+            headers: vec![],
+            body: None,
+            limits: Default::default(),
+            rate_limit: Default::default(),
+            cache: Some(HttpCache { ttl_seconds }),
+        }
+    }
+
+    fn header<'a>(request: &'a HttpRequest, name: &str) -> Option<&'a str> {
+        request.headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn fresh_responses_are_served_without_a_request() {
+        let (client, server, _) = client();
+        server.respond(200, &[("Cache-Control", "max-age=60")], "items");
+
+        assert_eq!(client.send(&get(None)).unwrap().cache, Some(CacheStatus::Miss));
+        let hit = client.send(&get(None)).unwrap();
+        assert_eq!((hit.status, hit.body.as_str(), hit.cache), (200, "items", Some(CacheStatus::Hit)));
+        assert_eq!(server.sent().len(), 1);
+    }
+
+    #[test]
+    fn responses_past_their_max_age_are_fetched_again() {
+        let (client, server, store) = client();
+        server.respond(200, &[("Cache-Control", "max-age=60")], "old");
+        server.respond(200, &[("Cache-Control", "max-age=60")], "new");
+
+        client.send(&get(None)).unwrap();
+        store.age(61);
+        let fetched = client.send(&get(None)).unwrap();
+        assert_eq!((fetched.body.as_str(), fetched.cache), ("new", Some(CacheStatus::Miss)));
+        assert_eq!(server.sent().len(), 2);
+    }
+
+    #[test]
+    fn etags_are_revalidated_with_if_none_match() {
+        let (client, server, _) = client();
+        server.respond(200, &[("ETag", "\"v1\"")], "items");
+        server.respond(304, &[], "");
+
+        client.send(&get(None)).unwrap();
+        let revalidated = client.send(&get(None)).unwrap();
+        assert_eq!(header(&server.sent()[1], "if-none-match"), Some("\"v1\""));
+        assert_eq!(
+            (revalidated.status, revalidated.body.as_str(), revalidated.cache),
+            (200, "items", Some(CacheStatus::Revalidated)),
+        );
+    }
+
+    #[test]
+    fn last_modified_is_revalidated_with_if_modified_since() {
+        let (client, server, _) = client();
+        server.respond(200, &[("Last-Modified", "Tue, 01 Feb 2022 10:00:00 GMT")], "items");
+        server.respond(304, &[], "");
+
+        client.send(&get(None)).unwrap();
+        let revalidated = client.send(&get(None)).unwrap();
+        assert_eq!(header(&server.sent()[1], "if-modified-since"), Some("Tue, 01 Feb 2022 10:00:00 GMT"));
+        assert_eq!((revalidated.body.as_str(), revalidated.cache), ("items", Some(CacheStatus::Revalidated)));
+    }
+
+    #[test]
+    fn no_store_responses_are_not_cached() {
+        let (client, server, _) = client();
+        server.respond(200, &[("Cache-Control", "no-store, max-age=60"), ("ETag", "\"v1\"")], "first");
+        server.respond(200, &[], "second");
+
+        client.send(&get(None)).unwrap();
+        let fetched = client.send(&get(None)).unwrap();
+        assert_eq!(header(&server.sent()[1], "if-none-match"), None);
+        assert_eq!((fetched.body.as_str(), fetched.cache), ("second", Some(CacheStatus::Miss)));
+    }
+
+    #[test]
+    fn ttl_seconds_keeps_responses_without_max_age_fresh() {
+        let (client, server, store) = client();
+        server.respond(200, &[], "old");
+        server.respond(200, &[], "new");
+
+        client.send(&get(Some(60))).unwrap();
+        assert_eq!(client.send(&get(Some(60))).unwrap().cache, Some(CacheStatus::Hit));
+        store.age(61);
+        assert_eq!(client.send(&get(Some(60))).unwrap().body, "new");
+    }
+
+    #[test]
+    fn not_modified_without_a_cached_response_is_fetched_again() {
+        let (client, server, _) = client();
+        server.respond(304, &[], "");
+        server.respond(200, &[], "items");
+
+        let mut request = get(None);
+        request.headers.push(("If-None-Match".to_owned(), "\"v1\"".to_owned()));
+        let fetched = client.send(&request).unwrap();
+        assert_eq!((fetched.status, fetched.body.as_str()), (200, "items"));
+        assert_eq!(header(&server.sent()[1], "if-none-match"), None);
+    }
+
+    #[test]
+    fn not_modified_twice_without_a_cached_response_is_an_error() {
+        let (client, server, _) = client();
+        server.respond(304, &[], "");
+        server.respond(304, &[], "");
+
+        let error = client.send(&get(None)).unwrap_err();
+        assert_eq!(error.to_string(), "GET https://example.com/items answered 304 Not Modified but no response is cached");
+    }
+}
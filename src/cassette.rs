@@ -0,0 +1,297 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::http::{HttpClient, HttpRequest, HttpResponse};
+use crate::template;
+
+const MULTIPART_BOUNDARY: &str = "BOUNDARY";
+
+/// Recorded http exchanges of a run.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// Request headers are left out, they hold the credentials of the recording run. Secrets in the query
+/// and the body are stored as their `{{secrets.NAME}}` placeholder.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+impl RecordedRequest {
+    fn new(request: &HttpRequest) -> Self {
+        // Multipart boundaries are random, so they are replaced to make the bodies of two runs comparable.
+        let boundary = request
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .and_then(|(_, value)| value.split_once("boundary="))
+            .map(|(_, boundary)| boundary);
+        let body = match (&request.body, boundary) {
+            (Some(body), Some(boundary)) => Some(body.replace(boundary, MULTIPART_BOUNDARY)),
+            (body, _) => body.clone(),
+        };
+        RecordedRequest {
+            method: request.method.clone(),
+            url: request.url.clone(),
+            query: request.query.iter().map(|(name, value)| (name.clone(), template::redact_secrets(value))).collect(),
+            body: body.map(|body| template::redact_secrets(&body)),
+        }
+    }
+}
+
+impl fmt::Display for RecordedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.url)?;
+        for (i, (name, value)) in self.query.iter().enumerate() {
+            write!(f, "{}{}={}", if i == 0 { '?' } else { '&' }, name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// Sends requests through another client and writes every exchange to the cassette file.
+#[derive(Debug)]
+pub struct RecordingClient {
+    inner: Box<dyn HttpClient>,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl RecordingClient {
+    /// Starts a new cassette, an existing file is replaced.
+    pub fn new(inner: Box<dyn HttpClient>, path: impl Into<PathBuf>) -> Self {
+        RecordingClient {
+            inner,
+            path: path.into(),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+}
+
+impl HttpClient for RecordingClient {
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Box<dyn Error>> {
+        let response = self.inner.send(request)?;
+        let mut cassette = self.cassette.lock().unwrap();
+        cassette.interactions.push(Interaction {
+            request: RecordedRequest::new(request),
+            response: RecordedResponse {
+                status: response.status,
+                headers: response.headers.clone(),
+                body: response.body.clone(),
+            },
+        });
+        // Written after every exchange, so a failing run still leaves what it recorded.
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&*cassette)?)?;
+        fs::rename(tmp, &self.path)?;
+        Ok(response)
+    }
+}
+
+/// Answers requests from a cassette without touching the network. Each interaction is used once, in order
+/// of recording among the ones matching method, url, query and body; any other request fails the step.
+#[derive(Debug)]
+pub struct ReplayClient {
+    interactions: Vec<Interaction>,
+    used: Mutex<Vec<bool>>,
+}
+
+impl ReplayClient {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path).map_err(|e| format!("cannot read cassette {}: {}", path.display(), e))?;
+        let cassette: Cassette = serde_json::from_str(&content)?;
+        Ok(ReplayClient {
+            used: Mutex::new(vec![false; cassette.interactions.len()]),
+            interactions: cassette.interactions,
+        })
+    }
+}
+
+impl HttpClient for ReplayClient {
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Box<dyn Error>> {
+        let recorded = RecordedRequest::new(request);
+        let mut used = self.used.lock().unwrap();
+        let position = self
+            .interactions
+            .iter()
+            .enumerate()
+            .position(|(i, interaction)| !used[i] && interaction.request == recorded);
+        match position {
+            None => Err(format!("unexpected request {}, it is not in the cassette", recorded).into()),
+            Some(i) => {
+                used[i] = true;
+                let response = &self.interactions[i].response;
+                Ok(HttpResponse {
+                    status: response.status,
+                    headers: response.headers.clone(),
+                    body: response.body.clone(),
+                    cache: None,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    /// Answers every request with a numbered body and counts what reaches it.
+    #[derive(Debug, Default)]
+    struct FakeClient {
+        sent: Mutex<u32>,
+    }
+
+    impl HttpClient for FakeClient {
+        fn send(&self, _request: &HttpRequest) -> Result<HttpResponse, Box<dyn Error>> {
+            let mut sent = self.sent.lock().unwrap();
+            *sent += 1;
+            Ok(HttpResponse {
+                status: 200,
+                headers: vec![("Content-Type".to_owned(), "text/plain".to_owned())],
+                body: format!("response {}", sent),
+                cache: None,
+            })
+        }
+    }
+
+    fn request(method: &str, url: &str) -> HttpRequest {
+        HttpRequest {
+            method: method.to_owned(),
+            url: url.to_owned(),
+            query: vec![],
+            headers: vec![],
+            body: None,
+            limits: Default::default(),
+            rate_limit: Default::default(),
+            cache: None,
+        }
+    }
+
+    fn cassette_path() -> PathBuf {
+        env::temp_dir().join(format!("psm-cassette-{}.json", Uuid::new_v4()))
+    }
+
+    fn record(path: &Path, requests: &[HttpRequest]) -> Vec<HttpResponse> {
+        let recorder = RecordingClient::new(Box::new(FakeClient::default()), path);
+        requests.iter().map(|request| recorder.send(request).unwrap()).collect()
+    }
+
+    #[test]
+    fn replays_what_was_recorded() {
+        let path = cassette_path();
+        let mut post = request("POST", "https://example.com/items");
+        post.body = Some("{\"name\":\"x\"}".to_owned());
+        let requests = vec![request("GET", "https://example.com/items"), post];
+        let recorded = record(&path, &requests);
+
+        let replay = ReplayClient::load(&path).unwrap();
+        for (request, response) in requests.iter().zip(recorded) {
+            assert_eq!(replay.send(request).unwrap(), response);
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn each_interaction_answers_one_request() {
+        let path = cassette_path();
+        let get = request("GET", "https://example.com/items");
+        record(&path, &[get.clone(), get.clone()]);
+
+        let replay = ReplayClient::load(&path).unwrap();
+        assert_eq!(replay.send(&get).unwrap().body, "response 1");
+        assert_eq!(replay.send(&get).unwrap().body, "response 2");
+        assert!(replay.send(&get).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn requests_match_on_method_url_query_and_body() {
+        let path = cassette_path();
+        let mut recorded = request("POST", "https://example.com/items");
+        recorded.query = vec![("page".to_owned(), "2".to_owned())];
+        recorded.body = Some("a=1".to_owned());
+        record(&path, &[recorded.clone()]);
+        let replay = ReplayClient::load(&path).unwrap();
+
+        let mut other_method = recorded.clone();
+        other_method.method = "PUT".to_owned();
+        let mut other_url = recorded.clone();
+        other_url.url = "https://example.com/other".to_owned();
+        let mut other_query = recorded.clone();
+        other_query.query = vec![("page".to_owned(), "3".to_owned())];
+        let mut other_body = recorded.clone();
+        other_body.body = Some("a=2".to_owned());
+        for request in [other_method, other_url, other_query, other_body] {
+            assert!(replay.send(&request).is_err(), "{:?} matched", request);
+        }
+
+        // Headers are not recorded, so they don't take part in matching.
+        let mut other_headers = recorded;
+        other_headers.headers = vec![("Authorization".to_owned(), "Bearer other".to_owned())];
+        assert_eq!(replay.send(&other_headers).unwrap().body, "response 1");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unexpected_requests_are_an_error() {
+        let path = cassette_path();
+        fs::write(&path, serde_json::to_string(&Cassette::default()).unwrap()).unwrap();
+
+        let error = ReplayClient::load(&path).unwrap().send(&request("GET", "https://example.com/items")).unwrap_err();
+        assert_eq!(error.to_string(), "unexpected request GET https://example.com/items, it is not in the cassette");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn secrets_are_recorded_as_placeholders() {
+        let path = cassette_path();
+        env::set_var("PSM_SECRET_CASSETTE_TEST_KEY", "s3cr3t-k3y");
+        let mut get = request("GET", "https://example.com/items");
+        get.query = vec![("key".to_owned(), "s3cr3t-k3y".to_owned())];
+        get.body = Some("{\"key\":\"s3cr3t-k3y\"}".to_owned());
+        record(&path, &[get]);
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("s3cr3t-k3y"));
+        assert!(content.contains("{{secrets.CASSETTE_TEST_KEY}}"));
+
+        // The replay run renders its own value of the secret, which matches the placeholder as well.
+        env::set_var("PSM_SECRET_CASSETTE_TEST_KEY", "other-k3y");
+        let mut replayed = request("GET", "https://example.com/items");
+        replayed.query = vec![("key".to_owned(), "other-k3y".to_owned())];
+        replayed.body = Some("{\"key\":\"other-k3y\"}".to_owned());
+        assert!(ReplayClient::load(&path).unwrap().send(&replayed).is_ok());
+        env::remove_var("PSM_SECRET_CASSETTE_TEST_KEY");
+        fs::remove_file(path).unwrap();
+    }
+}
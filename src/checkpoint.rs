@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use aws_sdk_dynamodb::Client;
+use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+
+use crate::context::ExecutionContext;
+
+pub const CHECKPOINT_TABLE: &str = "ParseStepCheckpoint";
+
+const EXECUTION_ID_KEY: &str = "execution_id";
+const WORKFLOW_ID_KEY: &str = "workflow_id";
+const CHECKPOINT_KEY: &str = "checkpoint";
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum CheckpointStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// State of an execution after its latest transition.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    #[serde(rename = "executionId")]
+    pub execution_id: String,
+    #[serde(rename = "workflowId")]
+    pub workflow_id: Option<String>,
+    pub status: CheckpointStatus,
+    /// Name of the last step that completed successfully.
+    #[serde(rename = "lastCompletedStep")]
+    pub last_completed_step: Option<String>,
+    /// Name of the step to run when the execution is resumed.
+    #[serde(rename = "nextStep")]
+    pub next_step: Option<String>,
+    pub context: ExecutionContext,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+}
+
+impl Checkpoint {
+    pub fn new(execution_id: &str, workflow_id: Option<String>, context: &ExecutionContext) -> Self {
+        Checkpoint {
+            execution_id: execution_id.to_owned(),
+            workflow_id,
+            status: CheckpointStatus::Running,
+            last_completed_step: None,
+            next_step: None,
+            context: context.clone(),
+            updated_at: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Persists checkpoints so an interrupted execution can be resumed.
+pub trait CheckpointStore: fmt::Debug {
+    fn save(&self, checkpoint: &Checkpoint) -> Result<(), Box<dyn Error>>;
+    fn load(&self, execution_id: &str) -> Result<Option<Checkpoint>, Box<dyn Error>>;
+}
+
+/// Keeps one `<execution_id>.json` file per execution in a directory.
+#[derive(Debug)]
+pub struct FileCheckpointStore {
+    dir: PathBuf,
+}
+
+impl FileCheckpointStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileCheckpointStore {
+            dir: dir.into(),
+        }
+    }
+
+    fn path(&self, execution_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", execution_id))
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn save(&self, checkpoint: &Checkpoint) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(&checkpoint.execution_id);
+        // Write next to the target and rename, so a crash never leaves a half written checkpoint.
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(checkpoint)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    fn load(&self, execution_id: &str) -> Result<Option<Checkpoint>, Box<dyn Error>> {
+        let path = self.path(execution_id);
+        match path.exists() {
+            false => Ok(None),
+            true => {
+                let content = fs::read_to_string(path)?;
+                Ok(Some(serde_json::from_str(&content)?))
+            }
+        }
+    }
+}
+
+/// Stores the checkpoint as a JSON document keyed by `execution_id`.
+#[derive(Debug)]
+pub struct DynamoCheckpointStore {
+    client: Client,
+    table: String,
+    handle: Handle,
+}
+
+impl DynamoCheckpointStore {
+    /// Must be created from within the tokio runtime that will serve the requests.
+    pub fn new(client: Client, table: &str) -> Self {
+        DynamoCheckpointStore {
+            client,
+            table: table.to_owned(),
+            handle: Handle::current(),
+        }
+    }
+}
+
+impl CheckpointStore for DynamoCheckpointStore {
+    fn save(&self, checkpoint: &Checkpoint) -> Result<(), Box<dyn Error>> {
+        let mut item = HashMap::new();
+        item.insert(EXECUTION_ID_KEY.to_owned(), AttributeValue::S(checkpoint.execution_id.clone()));
+        item.insert(CHECKPOINT_KEY.to_owned(), AttributeValue::S(serde_json::to_string(checkpoint)?));
+        if let Some(workflow_id) = &checkpoint.workflow_id {
+            item.insert(WORKFLOW_ID_KEY.to_owned(), AttributeValue::S(workflow_id.clone()));
+        }
+
+        let request = self.client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(item))
+            .send();
+
+        tokio::task::block_in_place(|| match self.handle.block_on(request) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.into()),
+        })
+    }
+
+    fn load(&self, execution_id: &str) -> Result<Option<Checkpoint>, Box<dyn Error>> {
+        let request = self.client
+            .get_item()
+            .table_name(&self.table)
+            .key(EXECUTION_ID_KEY, AttributeValue::S(execution_id.to_owned()))
+            .send();
+
+        let item = tokio::task::block_in_place(|| match self.handle.block_on(request) {
+            Ok(output) => Ok(output.item),
+            Err(e) => Err(Box::<dyn Error>::from(e)),
+        })?;
+
+        match item.as_ref().and_then(|item| item.get(CHECKPOINT_KEY)) {
+            Some(AttributeValue::S(checkpoint)) => Ok(Some(serde_json::from_str(checkpoint)?)),
+            _ => Ok(None),
+        }
+    }
+}
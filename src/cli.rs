@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+
+use crate::models::{HttpLimits, RateLimit};
+use crate::{checkpoint, dynamo, history, http};
+
+#[derive(Debug, Parser)]
+#[command(name = "psm", about = "Parse step definitions and run them as a state machine")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a workflow from local files or from DynamoDB
+    Run {
+        #[command(flatten)]
+        source: Source,
+        #[command(flatten)]
+        persistence: Persistence,
+        #[command(flatten)]
+        http: HttpArgs,
+    },
+    /// Continue an interrupted execution from its last checkpoint
+    Resume {
+        execution_id: String,
+        #[command(flatten)]
+        source: Source,
+        #[command(flatten)]
+        persistence: Persistence,
+        #[command(flatten)]
+        http: HttpArgs,
+    },
+    /// Check step definitions without running them, exits non-zero on errors
+    #[command(group(ArgGroup::new("input").required(true).args(["paths", "table"])))]
+    Validate {
+        /// JSON or YAML step files, or directories of them
+        paths: Vec<PathBuf>,
+        /// Validate the steps stored in DynamoDB instead of files
+        #[arg(long, num_args = 0..=1, default_missing_value = dynamo::DB_TABLE, conflicts_with = "paths")]
+        table: Option<String>,
+        /// Only validate this workflow of the table
+        #[arg(long, requires = "table")]
+        workflow: Option<String>,
+        #[arg(long, value_enum, default_value_t = Format::Human)]
+        format: Format,
+    },
+    /// Render the workflow as a Graphviz DOT or Mermaid flowchart
+    Graph {
+        #[command(flatten)]
+        source: Source,
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+        /// Overlay the path, durations, retries and failing step of this execution from the history
+        #[arg(long)]
+        execution: Option<String>,
+        #[arg(long, env = "PSM_HISTORY_TABLE", default_value = history::HISTORY_TABLE)]
+        history_table: String,
+    },
+    /// List the executions of a workflow
+    Executions {
+        workflow_id: String,
+        #[arg(long, env = "PSM_HISTORY_TABLE", default_value = history::HISTORY_TABLE)]
+        history_table: String,
+    },
+    /// Print the event log of an execution
+    Events {
+        execution_id: String,
+        #[arg(long, env = "PSM_HISTORY_TABLE", default_value = history::HISTORY_TABLE)]
+        history_table: String,
+    },
+}
+
+/// Where the step definitions are loaded from. Without `--file` or `--dir` they come from DynamoDB.
+#[derive(Debug, Args)]
+pub struct Source {
+    /// JSON or YAML file with one step or an array of steps, can be repeated
+    #[arg(long, conflicts_with_all = ["workflow", "table"])]
+    pub file: Vec<PathBuf>,
+    /// Directory with JSON and YAML step files, read in file name order
+    #[arg(long, conflicts_with_all = ["workflow", "table"])]
+    pub dir: Option<PathBuf>,
+    /// Load a single workflow from DynamoDB instead of scanning the whole table
+    #[arg(long)]
+    pub workflow: Option<String>,
+    /// DynamoDB table with the step definitions
+    #[arg(long, default_value = dynamo::DB_TABLE)]
+    pub table: String,
+}
+
+impl Source {
+    pub fn is_local(&self) -> bool {
+        !self.file.is_empty() || self.dir.is_some()
+    }
+}
+
+/// Where execution history and checkpoints are written. Runs from DynamoDB use the default tables,
+/// local runs only persist what is asked for explicitly.
+#[derive(Debug, Args)]
+pub struct Persistence {
+    /// DynamoDB table for the execution history
+    #[arg(long, env = "PSM_HISTORY_TABLE")]
+    pub history_table: Option<String>,
+    /// DynamoDB table for checkpoints
+    #[arg(long, env = "PSM_CHECKPOINT_TABLE", conflicts_with = "checkpoint_dir")]
+    pub checkpoint_table: Option<String>,
+    /// Directory for checkpoint files, used instead of DynamoDB
+    #[arg(long, env = "PSM_CHECKPOINT_DIR")]
+    pub checkpoint_dir: Option<PathBuf>,
+}
+
+impl Persistence {
+    pub fn history_table(&self, source: &Source) -> Option<String> {
+        match (&self.history_table, source.is_local()) {
+            (Some(table), _) => Some(table.clone()),
+            (None, false) => Some(history::HISTORY_TABLE.to_owned()),
+            (None, true) => None,
+        }
+    }
+
+    pub fn checkpoint_table(&self, source: &Source) -> Option<String> {
+        match (&self.checkpoint_table, &self.checkpoint_dir, source.is_local()) {
+            (Some(table), _, _) => Some(table.clone()),
+            (None, None, false) => Some(checkpoint::CHECKPOINT_TABLE.to_owned()),
+            _ => None,
+        }
+    }
+}
+
+/// Engine-wide limits of http steps, a step's own `limits` take precedence.
+#[derive(Debug, Args)]
+pub struct HttpArgs {
+    /// Connect timeout of http steps in milliseconds
+    #[arg(long, env = "PSM_HTTP_CONNECT_TIMEOUT_MS", default_value_t = http::DEFAULT_CONNECT_TIMEOUT_MS)]
+    pub http_connect_timeout_ms: u64,
+    /// Longest wait for response data in milliseconds
+    #[arg(long, env = "PSM_HTTP_READ_TIMEOUT_MS", default_value_t = http::DEFAULT_READ_TIMEOUT_MS)]
+    pub http_read_timeout_ms: u64,
+    /// Longest time a whole request may take in milliseconds, body included
+    #[arg(long, env = "PSM_HTTP_TIMEOUT_MS", default_value_t = http::DEFAULT_TIMEOUT_MS)]
+    pub http_timeout_ms: u64,
+    /// Redirects followed before the request fails, 0 returns the redirect response
+    #[arg(long, env = "PSM_HTTP_MAX_REDIRECTS", default_value_t = http::DEFAULT_MAX_REDIRECTS)]
+    pub http_max_redirects: u32,
+    /// Largest response body in bytes
+    #[arg(long, env = "PSM_HTTP_MAX_BODY_BYTES", default_value_t = http::DEFAULT_MAX_BODY_BYTES)]
+    pub http_max_body_bytes: u64,
+    /// Accepted response content type, e.g. text/html or text/*, can be repeated
+    #[arg(long = "http-content-type")]
+    pub http_content_types: Vec<String>,
+    /// Requests per second sent to a single host, unlimited when not set
+    #[arg(long, env = "PSM_HTTP_REQUESTS_PER_SECOND", value_parser = positive_rate)]
+    pub http_requests_per_second: Option<f64>,
+    /// Requests in flight to a single host at a time, unlimited when not set
+    #[arg(long, env = "PSM_HTTP_MAX_CONCURRENT", value_parser = clap::value_parser!(u32).range(1..))]
+    pub http_max_concurrent: Option<u32>,
+    /// Directory for cached responses of steps with a `cache` option, kept in memory for the run when not set
+    #[arg(long, env = "PSM_HTTP_CACHE_DIR")]
+    pub http_cache_dir: Option<PathBuf>,
+    /// Record every http exchange of the run into this cassette file
+    #[arg(long, conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+    /// Answer http steps from this cassette file instead of the network, unrecorded requests fail
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+}
+
+impl HttpArgs {
+    pub fn limits(&self) -> HttpLimits {
+        HttpLimits {
+            connect_timeout_ms: Some(self.http_connect_timeout_ms),
+            read_timeout_ms: Some(self.http_read_timeout_ms),
+            timeout_ms: Some(self.http_timeout_ms),
+            max_redirects: Some(self.http_max_redirects),
+            max_body_bytes: Some(self.http_max_body_bytes),
+            content_types: self.http_content_types.clone(),
+        }
+    }
+
+    pub fn rate_limit(&self) -> RateLimit {
+        RateLimit {
+            requests_per_second: self.http_requests_per_second,
+            max_concurrent: self.http_max_concurrent,
+        }
+    }
+}
+
+fn positive_rate(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(rate) if rate > 0.0 => Ok(rate),
+        _ => Err(format!("`{}` is not a number above 0", value)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Human,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
@@ -0,0 +1,24 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Outputs of the steps that already completed in an execution, keyed by step name.
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ExecutionContext {
+    pub outputs: HashMap<String, Value>,
+}
+
+impl ExecutionContext {
+    pub fn new() -> Self {
+        ExecutionContext::default()
+    }
+
+    pub fn insert(&mut self, step_name: &str, output: Value) {
+        self.outputs.insert(step_name.to_owned(), output);
+    }
+
+    pub fn get(&self, step_name: &str) -> Option<&Value> {
+        self.outputs.get(step_name)
+    }
+}
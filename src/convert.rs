@@ -0,0 +1,671 @@
+use std::error::Error;
+use std::fmt::{self, Write};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, FixedOffset, Locale, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use chrono_tz::Tz;
+use pure_rust_locales::{en_US, locale_match};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use serde_json::{Number, Value};
+
+use crate::context::ExecutionContext;
+use crate::models::{ConvertDefinition, ConvertSource, ConvertType, NumberFormat};
+use crate::template;
+
+/// Layouts tried after RFC 3339 for date sources without `fromFormat`.
+const DATE_FORMATS: [&str; 4] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%d"];
+
+/// Layouts with month names tried for date sources with a `locale` and without `fromFormat`, after its own layout.
+const LOCALE_DATE_FORMATS: [&str; 3] = ["%d %B %Y", "%B %d, %Y", "%A %d %B %Y"];
+
+/// Currency symbols dropped around numbers read with a number format.
+const CURRENCY_SYMBOLS: &str = "$€£¥₹₽₩₪₫₺₴₦฿₱¢";
+
+/// A value as one of the convert types. Sources are read into the datum of their `from` type, which is then
+/// coerced to the `to` type, so a new type needs one arm per coercion instead of one per pair of types.
+#[derive(Debug)]
+enum Datum {
+    Text(String),
+    /// Any `i64` or `u64`.
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Decimal(Decimal),
+    DateTime(DateTime<FixedOffset>),
+    Json(Value),
+    Bytes(Vec<u8>),
+}
+
+impl fmt::Display for Datum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Datum::Text(text) => write!(f, "{}", text),
+            Datum::Int(int) => write!(f, "{}", int),
+            Datum::Float(float) => write!(f, "{}", float),
+            Datum::Bool(bool) => write!(f, "{}", bool),
+            Datum::Decimal(decimal) => write!(f, "{}", decimal),
+            Datum::DateTime(date) => write!(f, "{}", date.to_rfc3339()),
+            Datum::Json(value) => write!(f, "{}", value),
+            Datum::Bytes(bytes) => write!(f, "{}", hex::encode(bytes)),
+        }
+    }
+}
+
+/// Output of a convert step.
+pub fn run(convert: &ConvertDefinition, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+    let source = match &convert.source {
+        ConvertSource::Literal(value) => value.clone(),
+        ConvertSource::Context(path) => match template::lookup(context, path) {
+            None => return Err(format!("`{}` is not in the execution context", path).into()),
+            Some(value) => value.clone(),
+        },
+        ConvertSource::Template(text) => Value::String(template::render(text, context)?),
+    };
+    let datum = read(convert.from, &source, convert)?;
+    let datum = coerce(datum, convert.to, convert)?;
+    render(datum, convert.to, convert)
+}
+
+/// The source as its `from` type. Encoded bytes and JSON text are decoded, the rest is coerced.
+fn read(from: ConvertType, source: &Value, convert: &ConvertDefinition) -> Result<Datum, Box<dyn Error>> {
+    match (from, source) {
+        (ConvertType::Json, Value::String(text)) => Ok(Datum::Json(parse_json(text)?)),
+        (ConvertType::Json, value) => Ok(Datum::Json(value.clone())),
+        (ConvertType::Base64, Value::String(text)) => {
+            Ok(Datum::Bytes(STANDARD.decode(text.trim()).map_err(|_| format!("`{}` is not valid base64", text))?))
+        }
+        (ConvertType::Hex, Value::String(text)) => {
+            Ok(Datum::Bytes(hex::decode(text.trim()).map_err(|_| format!("`{}` is not valid hex", text))?))
+        }
+        (from, value) => coerce(scalar(value), from, convert),
+    }
+}
+
+/// A JSON value as the datum of its own type. Arrays, objects and `null` are read as their JSON text.
+fn scalar(value: &Value) -> Datum {
+    match value {
+        Value::String(text) => Datum::Text(text.to_owned()),
+        Value::Bool(bool) => Datum::Bool(*bool),
+        // Numbers keep all their digits, so they are read as decimals unless they are integers.
+        Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(int), _) => Datum::Int(int as i128),
+            (_, Some(int)) => Datum::Int(int as i128),
+            _ => match Decimal::from_str_exact(&number.to_string()).or_else(|_| Decimal::from_scientific(&number.to_string())) {
+                Ok(decimal) => Datum::Decimal(decimal),
+                Err(_) => Datum::Float(number.as_f64().unwrap_or(f64::NAN)),
+            },
+        },
+        value => Datum::Text(value.to_string()),
+    }
+}
+
+fn coerce(datum: Datum, to: ConvertType, convert: &ConvertDefinition) -> Result<Datum, Box<dyn Error>> {
+    if let (ConvertType::Int | ConvertType::Float | ConvertType::Decimal, Datum::Text(text), Some(format)) = (to, &datum, &convert.number_format) {
+        return coerce(Datum::Decimal(parse_number(text, format)?), to, convert);
+    }
+    let coerced = match (to, datum) {
+        (ConvertType::String, datum) => Datum::Text(match datum {
+            Datum::Text(text) => text,
+            Datum::DateTime(date) => format_date(&date, convert)?,
+            Datum::Bytes(bytes) => String::from_utf8(bytes).map_err(|_| "the bytes are not valid UTF-8")?,
+            datum => datum.to_string(),
+        }),
+        (ConvertType::Json, datum) => Datum::Json(match datum {
+            Datum::Text(text) => parse_json(&text)?,
+            Datum::Int(int) => int_value(int)?,
+            Datum::Float(float) => Value::Number(finite(float)?),
+            Datum::Bool(bool) => Value::Bool(bool),
+            Datum::Decimal(decimal) => Value::String(decimal.to_string()),
+            Datum::DateTime(date) => Value::String(format_date(&date, convert)?),
+            Datum::Json(value) => value,
+            Datum::Bytes(bytes) => parse_json(&String::from_utf8(bytes).map_err(|_| "the bytes are not valid UTF-8")?)?,
+        }),
+        // A JSON value converts like the scalar it holds.
+        (to, Datum::Json(value)) => return coerce(scalar(&value), to, convert),
+        (ConvertType::Int, datum) => Datum::Int(match datum {
+            Datum::Text(text) => match text.trim().parse::<i128>() {
+                Ok(int) => in_range(int, &text)?,
+                // Digits that don't even fit an i128 overflow too.
+                Err(_) if is_integer(text.trim()) => return Err(overflow(&text)),
+                Err(_) => return Err(invalid(&text, to)),
+            },
+            Datum::Int(int) => int,
+            Datum::Float(float) if float.fract() == 0.0 => match float >= i64::MIN as f64 && float < u64::MAX as f64 {
+                true => float as i128,
+                false => return Err(overflow(&float)),
+            },
+            Datum::Bool(bool) => bool as i128,
+            Datum::Decimal(decimal) if decimal.fract().is_zero() => in_range(decimal.to_i128().unwrap_or(i128::MAX), &decimal)?,
+            Datum::DateTime(date) => date.timestamp() as i128,
+            datum => return Err(invalid(&datum, to)),
+        }),
+        (ConvertType::Float, datum) => Datum::Float(match datum {
+            Datum::Text(text) => text.trim().parse().map_err(|_| invalid(&text, to))?,
+            Datum::Int(int) => int as f64,
+            Datum::Float(float) => float,
+            Datum::Bool(bool) => bool as i64 as f64,
+            Datum::Decimal(decimal) => decimal.to_f64().ok_or_else(|| invalid(&decimal, to))?,
+            datum => return Err(invalid(&datum, to)),
+        }),
+        (ConvertType::Bool, datum) => Datum::Bool(match datum {
+            Datum::Text(text) => match text.trim().to_lowercase().as_str() {
+                "true" | "yes" | "1" => true,
+                "false" | "no" | "0" => false,
+                _ => return Err(invalid(&text, to)),
+            },
+            Datum::Int(int @ (0 | 1)) => int == 1,
+            Datum::Float(float) if float == 0.0 || float == 1.0 => float == 1.0,
+            Datum::Bool(bool) => bool,
+            Datum::Decimal(decimal) if decimal.is_zero() || decimal == Decimal::ONE => decimal == Decimal::ONE,
+            datum => return Err(invalid(&datum, to)),
+        }),
+        (ConvertType::Decimal, datum) => Datum::Decimal(match datum {
+            Datum::Text(text) => Decimal::from_str_exact(text.trim()).map_err(|_| invalid(&text, to))?,
+            Datum::Int(int) => Decimal::from_i128(int).ok_or_else(|| invalid(&int, to))?,
+            Datum::Float(float) => Decimal::from_f64(float).ok_or_else(|| invalid(&float, to))?,
+            Datum::Bool(bool) => Decimal::from(bool as i64),
+            Datum::Decimal(decimal) => decimal,
+            datum => return Err(invalid(&datum, to)),
+        }),
+        (ConvertType::DateTime, datum) => Datum::DateTime(match datum {
+            Datum::Text(text) => parse_date(&text, convert)?,
+            Datum::Int(int) => i64::try_from(int)
+                .ok()
+                .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+                .ok_or_else(|| invalid(&int, to))?
+                .fixed_offset(),
+            Datum::DateTime(date) => date,
+            datum => return Err(invalid(&datum, to)),
+        }),
+        (ConvertType::Base64 | ConvertType::Hex, datum) => Datum::Bytes(match datum {
+            Datum::Text(text) => text.into_bytes(),
+            Datum::Bytes(bytes) => bytes,
+            datum => return Err(invalid(&datum, to)),
+        }),
+    };
+    Ok(coerced)
+}
+
+fn render(datum: Datum, to: ConvertType, convert: &ConvertDefinition) -> Result<Value, Box<dyn Error>> {
+    let value = match datum {
+        Datum::Text(text) => Value::String(text),
+        Datum::Int(int) => int_value(int)?,
+        Datum::Float(float) => Value::Number(finite(float)?),
+        Datum::Bool(bool) => Value::Bool(bool),
+        Datum::Decimal(decimal) => Value::String(decimal.to_string()),
+        Datum::DateTime(date) => Value::String(format_date(&date, convert)?),
+        Datum::Json(value) => value,
+        Datum::Bytes(bytes) => match to {
+            ConvertType::Hex => Value::String(hex::encode(bytes)),
+            _ => Value::String(STANDARD.encode(bytes)),
+        },
+    };
+    Ok(value)
+}
+
+fn invalid(value: &impl fmt::Display, to: ConvertType) -> Box<dyn Error> {
+    format!("`{}` is not a valid {}", value, to.as_str()).into()
+}
+
+fn overflow(value: &impl fmt::Display) -> Box<dyn Error> {
+    format!("`{}` overflows a 64-bit integer", value.to_string().trim()).into()
+}
+
+fn is_integer(text: &str) -> bool {
+    let digits = text.strip_prefix(['-', '+']).unwrap_or(text);
+    !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Integers from `i64::MIN` to `u64::MAX`.
+fn in_range(int: i128, value: &impl fmt::Display) -> Result<i128, Box<dyn Error>> {
+    match int >= i64::MIN as i128 && int <= u64::MAX as i128 {
+        true => Ok(int),
+        false => Err(overflow(value)),
+    }
+}
+
+fn int_value(int: i128) -> Result<Value, Box<dyn Error>> {
+    match (i64::try_from(int), u64::try_from(int)) {
+        (Ok(int), _) => Ok(Value::from(int)),
+        (_, Ok(int)) => Ok(Value::from(int)),
+        _ => Err(overflow(&int)),
+    }
+}
+
+fn finite(float: f64) -> Result<Number, Box<dyn Error>> {
+    Number::from_f64(float).ok_or_else(|| format!("`{}` is not a finite number", float).into())
+}
+
+fn parse_json(text: &str) -> Result<Value, Box<dyn Error>> {
+    serde_json::from_str(text).map_err(|e| format!("`{}` is not valid JSON: {}", text, e).into())
+}
+
+/// The number format of `locale`, with the separators given in the definition in place of the locale's.
+/// Without a locale `.` separates decimals, and `,` groups thousands unless it separates decimals.
+pub fn number_format(locale: Option<Locale>, decimal_separator: Option<char>, thousands_separator: Option<Option<char>>) -> NumberFormat {
+    let (locale_decimal, locale_thousands, currencies) = match locale {
+        None => (None, None, vec![]),
+        Some(locale) => {
+            let currencies = [locale_match!(locale => LC_MONETARY::CURRENCY_SYMBOL), locale_match!(locale => LC_MONETARY::INT_CURR_SYMBOL)]
+                .iter()
+                .map(|currency| currency.trim().to_owned())
+                .filter(|currency| !currency.is_empty())
+                .collect();
+            let thousands = locale_match!(locale => LC_NUMERIC::THOUSANDS_SEP).chars().next();
+            (locale_match!(locale => LC_NUMERIC::DECIMAL_POINT).chars().next(), Some(thousands), currencies)
+        }
+    };
+    let decimal_separator = decimal_separator.or(locale_decimal).unwrap_or('.');
+    let thousands_separator = thousands_separator.or(locale_thousands).unwrap_or(match decimal_separator {
+        ',' => Some('.'),
+        _ => Some(','),
+    });
+    NumberFormat {
+        decimal_separator,
+        thousands_separator,
+        currencies,
+    }
+}
+
+/// Reads numbers like `1.234,56 €`, `-$1,234.56`, `(12.00)` or `12,5 %`. Percentages are divided by 100.
+fn parse_number(text: &str, format: &NumberFormat) -> Result<Decimal, Box<dyn Error>> {
+    let invalid = || -> Box<dyn Error> { format!("`{}` is not a valid number", text).into() };
+    let mut rest = text.trim();
+    let mut negative = false;
+    if let Some(inner) = rest.strip_prefix('(').and_then(|inner| inner.strip_suffix(')')) {
+        negative = true;
+        rest = inner.trim();
+    }
+
+    let start = rest.find(|c: char| c.is_ascii_digit() || c == format.decimal_separator).ok_or_else(invalid)?;
+    let end = rest.rfind(|c: char| c.is_ascii_digit()).ok_or_else(invalid)? + 1;
+    let mut percent = false;
+    for affix in [&rest[..start], &rest[end..]] {
+        let mut currency = String::new();
+        for c in affix.chars().filter(|c| !c.is_whitespace()) {
+            match c {
+                '-' | '\u{2212}' => negative = !negative,
+                '+' => {}
+                '%' => percent = true,
+                c => currency.push(c),
+            }
+        }
+        let known = currency.is_empty()
+            || format.currencies.contains(&currency)
+            || currency.chars().all(|c| CURRENCY_SYMBOLS.contains(c))
+            || (currency.len() == 3 && currency.chars().all(|c| c.is_ascii_uppercase()));
+        if !known {
+            return Err(invalid());
+        }
+    }
+
+    // Groups of the integer part, then the fraction.
+    let mut groups = vec![String::new()];
+    let mut fraction: Option<String> = None;
+    for c in rest[start..end].chars() {
+        match (c, &mut fraction) {
+            (c, Some(fraction)) if c.is_ascii_digit() => fraction.push(c),
+            (c, None) if c.is_ascii_digit() => groups.last_mut().unwrap().push(c),
+            (c, None) if c == format.decimal_separator => fraction = Some(String::new()),
+            (c, None) if Some(c) == format.thousands_separator || c.is_whitespace() => {
+                if !groups.last().unwrap().is_empty() {
+                    groups.push(String::new());
+                }
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    // `1,5` is not a grouped 15. Groups after the first hold 3 digits, or 2 in Indian grouping like `1,23,456`.
+    let grouped = groups.iter().skip(1).all(|group| group.len() == 2 || group.len() == 3) && groups.last().is_some_and(|group| groups.len() == 1 || group.len() == 3);
+    if !grouped {
+        return Err(invalid());
+    }
+
+    let digits = format!("{}.{}", groups.concat(), fraction.unwrap_or_default());
+    let digits = digits.trim_end_matches('.');
+    let digits = if digits.starts_with('.') { format!("0{}", digits) } else { digits.to_owned() };
+    let mut number = Decimal::from_str_exact(&digits).map_err(|_| invalid())?;
+    if percent {
+        number = number.checked_div(Decimal::ONE_HUNDRED).ok_or_else(invalid)?;
+    }
+    if negative {
+        number = -number;
+    }
+    Ok(number)
+}
+
+/// Replaces the month and day names of `locale` by their English names, which `chrono` parses. Abbreviations
+/// come first, as `%b` only reads abbreviations while `%B` reads both.
+fn english_names(text: &str, locale: Locale) -> String {
+    let tables: [(&[&str], &[&str]); 5] = [
+        (locale_match!(locale => LC_TIME::ABMON), en_US::LC_TIME::ABMON),
+        (locale_match!(locale => LC_TIME::MON), en_US::LC_TIME::MON),
+        (locale_match!(locale => LC_TIME::ALT_MON).unwrap_or_default(), en_US::LC_TIME::MON),
+        (locale_match!(locale => LC_TIME::ABDAY), en_US::LC_TIME::ABDAY),
+        (locale_match!(locale => LC_TIME::DAY), en_US::LC_TIME::DAY),
+    ];
+    let chars: Vec<char> = text.chars().collect();
+    let mut english = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_alphabetic() {
+            english.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        let end = (i..chars.len()).find(|&j| !chars[j].is_alphabetic()).unwrap_or(chars.len());
+        let word: String = chars[i..end].iter().collect();
+        let lower = word.to_lowercase();
+        let found = tables.iter().find_map(|(names, english)| {
+            names.iter().position(|name| name.to_lowercase().trim_end_matches('.') == lower).map(|k| (english[k], names[k].ends_with('.')))
+        });
+        match found {
+            None => {
+                english.push_str(&word);
+                i = end;
+            }
+            // The dot of an abbreviation like `févr.` goes with the name.
+            Some((name, dotted)) => {
+                english.push_str(name);
+                i = match dotted && chars.get(end) == Some(&'.') {
+                    true => end + 1,
+                    false => end,
+                };
+            }
+        }
+    }
+    english
+}
+
+/// Dates without an offset are in `timezone`, or UTC.
+fn parse_date(text: &str, convert: &ConvertDefinition) -> Result<DateTime<FixedOffset>, Box<dyn Error>> {
+    let original = text.trim();
+    let english = convert.locale.map(|locale| english_names(original, locale));
+    let text = english.as_deref().unwrap_or(original);
+    let zone = convert.timezone.unwrap_or(Tz::UTC);
+    let formats = match &convert.from_format {
+        Some(format) => vec![format.as_str()],
+        None => {
+            if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+                return Ok(date);
+            }
+            let mut formats = DATE_FORMATS.to_vec();
+            if let Some(locale) = convert.locale {
+                formats.push(locale_match!(locale => LC_TIME::D_FMT));
+                formats.extend(LOCALE_DATE_FORMATS);
+            }
+            formats
+        }
+    };
+    for format in formats {
+        if let Ok(date) = DateTime::parse_from_str(text, format) {
+            return Ok(date);
+        }
+        let local = match NaiveDateTime::parse_from_str(text, format) {
+            Ok(local) => local,
+            Err(_) => match NaiveDate::parse_from_str(text, format) {
+                Ok(date) => date.and_time(NaiveTime::MIN),
+                Err(_) => continue,
+            },
+        };
+        return match zone.from_local_datetime(&local).earliest() {
+            Some(date) => Ok(date.fixed_offset()),
+            None => Err(format!("`{}` does not exist in {}", original, zone).into()),
+        };
+    }
+    Err(format!("`{}` is not a valid datetime", original).into())
+}
+
+/// Dates are output in `timezone`, or with the offset they were read with.
+fn format_date(date: &DateTime<FixedOffset>, convert: &ConvertDefinition) -> Result<String, Box<dyn Error>> {
+    let date = match convert.timezone {
+        Some(zone) => date.with_timezone(&zone).fixed_offset(),
+        None => *date,
+    };
+    match &convert.to_format {
+        None => Ok(date.to_rfc3339()),
+        Some(format) => {
+            let mut text = String::new();
+            let written = match convert.locale {
+                Some(locale) => write!(text, "{}", date.format_localized(format, locale)),
+                None => write!(text, "{}", date.format(format)),
+            };
+            written.map_err(|_| format!("cannot format {} as `{}`", date.to_rfc3339(), format))?;
+            Ok(text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::StepDefinition;
+    use crate::serializer::{self, StepError};
+
+    const TYPES: [&str; 9] = ["string", "int", "float", "bool", "decimal", "datetime", "json", "base64", "hex"];
+
+    /// Loads a convert step written the way it is in a step file.
+    fn load(convert: Value) -> Result<ConvertDefinition, StepError> {
+        let step = serializer::parse_step(&json!({
+            "uuid": "2639f3c3-9e49-4802-92e3-7b8a68c25e4d",
+            "name": "convert",
+            "type": "convert",
+            "createdAt": "2022-01-30T14:47:25.869Z",
+            "errorOnFail": "Failed to convert",
+            "next": "",
+            "convert": convert,
+        }))?;
+        match step.definition {
+            StepDefinition::Convert(convert) => Ok(convert),
+            definition => panic!("{:?} is not a convert step", definition),
+        }
+    }
+
+    fn convert(from: &str, to: &str, source: Value) -> Result<Value, Box<dyn Error>> {
+        run(&load(json!({"from": from, "to": to, "source": source}))?, &ExecutionContext::new())
+    }
+
+    #[test]
+    fn conversion_matrix() {
+        // Every supported pair with a source of the `from` type and the documented output.
+        let supported = [
+            ("string", "int", json!("42"), json!(42)),
+            ("string", "float", json!("1.5"), json!(1.5)),
+            ("string", "bool", json!("yes"), json!(true)),
+            ("string", "decimal", json!("1.10"), json!("1.10")),
+            ("string", "datetime", json!("2022-01-30 14:40:46"), json!("2022-01-30T14:40:46+00:00")),
+            ("string", "json", json!("{\"a\": [1]}"), json!({"a": [1]})),
+            ("string", "base64", json!("hi"), json!("aGk=")),
+            ("string", "hex", json!("hi"), json!("6869")),
+            ("int", "string", json!(42), json!("42")),
+            ("int", "float", json!(42), json!(42.0)),
+            ("int", "bool", json!(1), json!(true)),
+            ("int", "decimal", json!(42), json!("42")),
+            ("int", "datetime", json!(1643553646), json!("2022-01-30T14:40:46+00:00")),
+            ("int", "json", json!(42), json!(42)),
+            ("float", "string", json!(1.5), json!("1.5")),
+            ("float", "int", json!(2.0), json!(2)),
+            ("float", "bool", json!(0.0), json!(false)),
+            ("float", "decimal", json!(1.5), json!("1.5")),
+            ("float", "json", json!(1.5), json!(1.5)),
+            ("bool", "string", json!(true), json!("true")),
+            ("bool", "int", json!(true), json!(1)),
+            ("bool", "float", json!(true), json!(1.0)),
+            ("bool", "decimal", json!(false), json!("0")),
+            ("bool", "json", json!(true), json!(true)),
+            ("decimal", "string", json!("1.10"), json!("1.10")),
+            ("decimal", "int", json!("3.0"), json!(3)),
+            ("decimal", "float", json!("1.25"), json!(1.25)),
+            ("decimal", "bool", json!("1"), json!(true)),
+            ("decimal", "json", json!("1.10"), json!("1.10")),
+            ("datetime", "string", json!("2022-01-30T14:40:46+01:00"), json!("2022-01-30T14:40:46+01:00")),
+            ("datetime", "int", json!("2022-01-30T14:40:46Z"), json!(1643553646)),
+            ("datetime", "datetime", json!("2022-01-30 14:40:46"), json!("2022-01-30T14:40:46+00:00")),
+            ("datetime", "json", json!("2022-01-30"), json!("2022-01-30T00:00:00+00:00")),
+            ("json", "string", json!({"a": 1}), json!("{\"a\":1}")),
+            ("json", "int", json!("42"), json!(42)),
+            ("json", "float", json!(1.5), json!(1.5)),
+            ("json", "bool", json!(true), json!(true)),
+            ("json", "decimal", json!("\"2.50\""), json!("2.50")),
+            ("json", "datetime", json!("\"2022-01-30\""), json!("2022-01-30T00:00:00+00:00")),
+            ("json", "base64", json!("\"hi\""), json!("aGk=")),
+            ("json", "hex", json!("\"hi\""), json!("6869")),
+            ("base64", "string", json!("aGk="), json!("hi")),
+            ("base64", "hex", json!("aGk="), json!("6869")),
+            ("base64", "json", json!("MQ=="), json!(1)),
+            ("hex", "string", json!("6869"), json!("hi")),
+            ("hex", "base64", json!("6869"), json!("aGk=")),
+            ("hex", "json", json!("7b7d"), json!({})),
+        ];
+
+        for from in TYPES {
+            for to in TYPES {
+                let case = supported.iter().find(|(case_from, case_to, _, _)| *case_from == from && *case_to == to);
+                match case {
+                    Some((_, _, source, expected)) => {
+                        let output = convert(from, to, source.clone()).unwrap_or_else(|e| panic!("{} to {}: {}", from, to, e));
+                        assert_eq!(&output, expected, "{} to {}", from, to);
+                    }
+                    None => {
+                        let error = load(json!({"from": from, "to": to, "source": ""})).unwrap_err();
+                        assert_eq!(error, StepError::new("convert.to", format!("cannot convert from {} to {}", from, to)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Numbers written the way a step file or a parsed document holds them, digits beyond `u64` included.
+    fn number(digits: &str) -> Value {
+        serde_json::from_str(digits).unwrap()
+    }
+
+    #[test]
+    fn int_literals_cover_i64_min_to_u64_max() {
+        for digits in ["-9223372036854775808", "18446744073709551615"] {
+            assert_eq!(convert("int", "string", number(digits)).unwrap(), json!(digits));
+            assert_eq!(convert("int", "json", number(digits)).unwrap(), number(digits));
+        }
+        for digits in ["-9223372036854775809", "18446744073709551616"] {
+            let error = load(json!({"from": "int", "to": "string", "source": number(digits)})).unwrap_err();
+            assert_eq!(error, StepError::new("convert.source", "convert –» source overflows a 64-bit integer"));
+        }
+    }
+
+    #[test]
+    fn int_text_out_of_range_fails_the_step() {
+        assert_eq!(convert("string", "int", json!("-9223372036854775808")).unwrap(), json!(i64::MIN));
+        assert_eq!(convert("string", "int", json!(" 18446744073709551615 ")).unwrap(), json!(u64::MAX));
+        for digits in ["-9223372036854775809", "18446744073709551616", "999999999999999999999999999999999999999999"] {
+            let error = convert("string", "int", json!(digits)).unwrap_err();
+            assert_eq!(error.to_string(), format!("`{}` overflows a 64-bit integer", digits));
+        }
+    }
+
+    #[test]
+    fn int_context_values_out_of_range_fail_the_step() {
+        let mut context = ExecutionContext::new();
+        context.insert("parse", json!({"max": number("18446744073709551615"), "over": number("18446744073709551616"), "under": number("-9223372036854775809")}));
+        let convert = |path: &str| {
+            let definition = load(json!({"from": "int", "to": "string", "source": format!("{{{{context.parse.{}}}}}", path)})).unwrap();
+            run(&definition, &context)
+        };
+        assert_eq!(convert("max").unwrap(), json!("18446744073709551615"));
+        assert_eq!(convert("over").unwrap_err().to_string(), "`18446744073709551616` overflows a 64-bit integer");
+        assert_eq!(convert("under").unwrap_err().to_string(), "`-9223372036854775809` overflows a 64-bit integer");
+    }
+
+    #[test]
+    fn floats_out_of_the_int_range_fail_the_step() {
+        assert_eq!(convert("float", "int", json!(-9.2e18)).unwrap(), json!(-9_200_000_000_000_000_000i64));
+        for float in [1e20, -1e19] {
+            let error = convert("float", "int", json!(float)).unwrap_err();
+            assert_eq!(error.to_string(), format!("`{}` overflows a 64-bit integer", float));
+        }
+    }
+
+    #[test]
+    fn decimals_hold_28_digits() {
+        let digits = "1234567890.123456789012345678";
+        assert_eq!(convert("decimal", "string", number(digits)).unwrap(), json!(digits));
+        assert_eq!(convert("string", "decimal", json!(digits)).unwrap(), json!(digits));
+
+        let digits = "1234567890.12345678901234567890";
+        let error = load(json!({"from": "decimal", "to": "string", "source": number(digits)})).unwrap_err();
+        assert_eq!(error, StepError::new("convert.source", "convert –» source has more digits than a decimal holds (28)"));
+        let error = convert("string", "decimal", json!(digits)).unwrap_err();
+        assert_eq!(error.to_string(), format!("`{}` is not a valid decimal", digits));
+    }
+
+    fn localized(to: &str, source: &str, options: Value) -> Result<Value, Box<dyn Error>> {
+        let mut convert = json!({"from": "string", "to": to, "source": source});
+        convert.as_object_mut().unwrap().extend(options.as_object().unwrap().clone());
+        run(&load(convert)?, &ExecutionContext::new())
+    }
+
+    #[test]
+    fn numbers_are_read_the_way_the_locale_writes_them() {
+        let de = json!({"locale": "de-DE"});
+        assert_eq!(localized("decimal", "1.234,56 €", de.clone()).unwrap(), json!("1234.56"));
+        assert_eq!(localized("float", "-1.234,5", de.clone()).unwrap(), json!(-1234.5));
+        assert_eq!(localized("int", "1.234 EUR", de.clone()).unwrap(), json!(1234));
+        assert_eq!(localized("decimal", "12,5 %", de).unwrap(), json!("0.125"));
+        assert_eq!(localized("decimal", "(12.00)", json!({"locale": "en-US"})).unwrap(), json!("-12.00"));
+        assert_eq!(localized("decimal", "$1,234.56", json!({"locale": "en_US"})).unwrap(), json!("1234.56"));
+    }
+
+    #[test]
+    fn indian_grouping_is_read() {
+        assert_eq!(localized("decimal", "₹1,23,456.78", json!({"locale": "en-IN"})).unwrap(), json!("123456.78"));
+        assert_eq!(localized("int", "12,34,567", json!({"decimalSeparator": "."})).unwrap(), json!(1234567));
+    }
+
+    #[test]
+    fn separators_override_the_locale() {
+        // Without grouping a separator in the digits is not a number.
+        let ungrouped = json!({"locale": "de-DE", "thousandsSeparator": ""});
+        assert_eq!(localized("decimal", "1234,5", ungrouped.clone()).unwrap(), json!("1234.5"));
+        assert!(localized("decimal", "1.234,5", ungrouped).is_err());
+
+        let swiss = json!({"decimalSeparator": ".", "thousandsSeparator": "'"});
+        assert_eq!(localized("decimal", "1'234.50", swiss).unwrap(), json!("1234.50"));
+    }
+
+    #[test]
+    fn numbers_in_another_format_fail_the_step() {
+        let error = localized("decimal", "1.234,56 €", json!({"locale": "fr-FR"})).unwrap_err();
+        assert_eq!(error.to_string(), "`1.234,56 €` is not a valid number");
+        assert_eq!(localized("decimal", "1 234,56 €", json!({"locale": "fr-FR"})).unwrap(), json!("1234.56"));
+        // `1,5` is not a grouped 15.
+        assert!(localized("int", "1,5", json!({"locale": "en-US"})).is_err());
+        assert!(localized("decimal", "12 apples", json!({"locale": "en-US"})).is_err());
+    }
+
+    #[test]
+    fn dates_are_read_with_the_locale_names() {
+        let french = json!({"locale": "fr-FR", "fromFormat": "%d %B %Y"});
+        assert_eq!(localized("datetime", "3 février 2022", french.clone()).unwrap(), json!("2022-02-03T00:00:00+00:00"));
+        assert_eq!(localized("datetime", "3 Février 2022", french).unwrap(), json!("2022-02-03T00:00:00+00:00"));
+
+        let abbreviated = json!({"locale": "fr-FR", "fromFormat": "%d %b %Y"});
+        assert_eq!(localized("datetime", "3 févr. 2022", abbreviated).unwrap(), json!("2022-02-03T00:00:00+00:00"));
+
+        // Without fromFormat the locale's layouts are tried.
+        assert_eq!(localized("datetime", "jeudi 3 février 2022", json!({"locale": "fr"})).unwrap(), json!("2022-02-03T00:00:00+00:00"));
+        assert!(localized("datetime", "3 février 2022", json!({})).is_err());
+    }
+
+    #[test]
+    fn to_format_writes_the_locale_names() {
+        let convert = json!({"from": "datetime", "to": "string", "source": "2022-02-03T10:00:00+00:00", "toFormat": "%A %-d %B %Y"});
+        assert_eq!(run(&load(convert.clone()).unwrap(), &ExecutionContext::new()).unwrap(), json!("Thursday 3 February 2022"));
+
+        let mut german = convert;
+        german["locale"] = json!("de-DE");
+        assert_eq!(run(&load(german).unwrap(), &ExecutionContext::new()).unwrap(), json!("Donnerstag 3 Februar 2022"));
+    }
+}
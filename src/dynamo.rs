@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::env;
+
+use aws_config::meta::region::RegionProviderChain;
+use aws_sdk_dynamodb::{Client, Endpoint, Error as DynamoDBError};
+use aws_sdk_dynamodb::model::AttributeValue;
+use serde_json::Value;
+
+use crate::models::{
+    ConvertStrType,
+    HttpStrType,
+    ParseStrType,
+    StrStep,
+};
+
+pub const DB_TABLE: &str = "ParseStep";
+
+/// Partition key of the steps table. Every step belongs to exactly one workflow.
+pub const WORKFLOW_ID_KEY: &str = "workflow_id";
+/// Sort key of the steps table. Step names are unique within a workflow.
+pub const NAME_KEY: &str = "name";
+
+/// Overrides the DynamoDB endpoint, e.g. `http://localhost:8000` for DynamoDB Local.
+const ENDPOINT_ENV: &str = "DYNAMODB_ENDPOINT";
+
+pub async fn client() -> Client {
+    let region_provider = RegionProviderChain::default_provider().or_else("us-east-1");
+    let config = aws_config::from_env().region(region_provider).load().await;
+
+    match env::var(ENDPOINT_ENV) {
+        Ok(endpoint) => {
+            let uri = endpoint.parse().expect(ENDPOINT_ENV);
+            let dynamo_config = aws_sdk_dynamodb::config::Builder::from(&config)
+                .endpoint_resolver(Endpoint::immutable(uri))
+                .build();
+            Client::from_conf(dynamo_config)
+        }
+        Err(_) => Client::new(&config),
+    }
+}
+
+/// Scans the whole table, following `last_evaluated_key` until every page is read.
+pub async fn scan_steps(client: &Client, table: &str) -> Result<Vec<StrStep>, DynamoDBError> {
+    let mut steps = vec![];
+    let mut start_key = None;
+
+    loop {
+        let resp = client
+            .scan()
+            .table_name(table)
+            .set_exclusive_start_key(start_key)
+            .send()
+            .await?;
+
+        if let Some(items) = resp.items {
+            steps.extend(items.into_iter().map(value_to_item));
+        }
+
+        match resp.last_evaluated_key {
+            None => break,
+            Some(key) => start_key = Some(key),
+        }
+    }
+
+    Ok(steps)
+}
+
+/// Loads the steps of a single workflow with `query` on the partition key, page by page.
+pub async fn query_workflow(client: &Client, table: &str, workflow_id: &str) -> Result<Vec<StrStep>, DynamoDBError> {
+    let mut steps = vec![];
+    let mut start_key = None;
+
+    loop {
+        let resp = client
+            .query()
+            .table_name(table)
+            .key_condition_expression("#wf = :wf")
+            .expression_attribute_names("#wf", WORKFLOW_ID_KEY)
+            .expression_attribute_values(":wf", AttributeValue::S(workflow_id.to_owned()))
+            .set_exclusive_start_key(start_key)
+            .send()
+            .await?;
+
+        if let Some(items) = resp.items {
+            steps.extend(items.into_iter().map(value_to_item));
+        }
+
+        match resp.last_evaluated_key {
+            None => break,
+            Some(key) => start_key = Some(key),
+        }
+    }
+
+    Ok(steps)
+}
+
+pub fn value_to_item(item: HashMap<String, AttributeValue>) -> StrStep {
+    let mut typ_str = String::new();
+    let mut uuid = String::new();
+    let mut workflow_id = None;
+    let mut name = String::new();
+    let mut error_on_fail = String::new();
+    let mut created_at = String::new();
+    let mut next = None;
+    let mut retries = None;
+    let mut http = None;
+    let mut parse = None;
+    let mut convert = None;
+    for (key, value) in item.iter() {
+        match (key.as_str(), value) {
+            ("type", AttributeValue::S(value)) => {
+                typ_str = value.to_owned();
+            }
+            ("uuid", AttributeValue::S(value)) => {
+                uuid = value.to_owned();
+            }
+            (WORKFLOW_ID_KEY, AttributeValue::S(value)) => {
+                workflow_id = Some(value.to_owned());
+            }
+            ("error_on_fail", AttributeValue::S(value)) => {
+                error_on_fail = value.to_owned();
+            }
+            ("next", AttributeValue::S(value)) if !value.is_empty() => {
+                next = Some(value.to_owned());
+            }
+            ("retries", value @ AttributeValue::N(_)) => {
+                retries = Some(attribute_to_json(value));
+            }
+            ("created_at", AttributeValue::S(value)) => {
+                created_at = value.to_owned();
+            }
+            (NAME_KEY, AttributeValue::S(value)) => {
+                name = value.to_owned();
+            }
+            ("http", AttributeValue::M(http_)) => {
+                let typ = extract_s_or_default(http_.get("type"));
+                let url = extract_s_or_default(http_.get("url"));
+                // Missing bodies stay missing, the serializer decides whether the method needs one.
+                let body = http_.get("body").map(attribute_to_json);
+                let form = http_.get("form").map(attribute_to_json);
+                let multipart = http_.get("multipart").map(attribute_to_json);
+                let headers = http_.get("headers").map(attribute_to_json);
+                let query = http_.get("query").map(attribute_to_json);
+                let auth = http_.get("auth").map(attribute_to_json);
+                let expect = http_.get("expect").map(attribute_to_json);
+                let extract = http_.get("extract").map(attribute_to_json);
+                let limits = http_.get("limits").map(attribute_to_json);
+                let rate_limit = http_.get("rate_limit").map(attribute_to_json);
+                let cache = http_.get("cache").map(attribute_to_json);
+                http = match typ.to_lowercase().as_str() {
+                    "get" | "head" => {
+                        Some(HttpStrType {
+                            typ,
+                            url,
+                            body: None,
+                            form: None,
+                            multipart: None,
+                            headers,
+                            query,
+                            auth,
+                            expect,
+                            extract,
+                            limits,
+                            rate_limit,
+                            cache,
+                        })
+                    }
+                    "post" | "put" | "patch" | "delete" => {
+                        Some(HttpStrType {
+                            typ,
+                            url,
+                            body,
+                            form,
+                            multipart,
+                            headers,
+                            query,
+                            auth,
+                            expect,
+                            extract,
+                            limits,
+                            rate_limit,
+                            cache,
+                        })
+                    }
+                    _ => None
+                };
+            }
+            ("parse", AttributeValue::M(parse_)) => {
+                let typ = extract_s_or_default(parse_.get("type"));
+                let content = match parse_.get("content") {
+                    Some(AttributeValue::S(content)) => Some(content.to_owned()),
+                    _ => None
+                };
+                let options = parse_
+                    .iter()
+                    .filter(|(key, _)| key.as_str() != "type" && key.as_str() != "content")
+                    .map(|(key, value)| (key.to_owned(), attribute_to_json(value)))
+                    .collect();
+                parse = Some(ParseStrType {
+                    typ,
+                    content,
+                    options,
+                })
+            }
+            ("convert", AttributeValue::M(convert_)) => {
+                let options = convert_
+                    .iter()
+                    .filter(|(key, _)| !["type", "from", "to", "source"].contains(&key.as_str()))
+                    .map(|(key, value)| (key.to_owned(), attribute_to_json(value)))
+                    .collect();
+                convert = Some(ConvertStrType {
+                    typ: extract_s_or_default(convert_.get("type")),
+                    from: extract_s_or_default(convert_.get("from")),
+                    to: extract_s_or_default(convert_.get("to")),
+                    source: convert_.get("source").map(attribute_to_json),
+                    options,
+                });
+            }
+            _ => {}
+        }
+    }
+    StrStep {
+        typ: typ_str,
+        uuid,
+        workflow_id,
+        name,
+        error_on_fail,
+        created_at,
+        next,
+        retries,
+        http,
+        parse,
+        convert,
+    }
+}
+
+/// Plain JSON for nested `M` and `L` attributes, e.g. http headers or auth objects.
+pub fn attribute_to_json(value: &AttributeValue) -> Value {
+    match value {
+        AttributeValue::S(value) => Value::String(value.to_owned()),
+        AttributeValue::N(value) => serde_json::from_str::<Value>(value).unwrap_or_else(|_| Value::String(value.to_owned())),
+        AttributeValue::Bool(value) => Value::Bool(*value),
+        AttributeValue::L(values) => Value::Array(values.iter().map(attribute_to_json).collect()),
+        AttributeValue::M(values) => {
+            Value::Object(values.iter().map(|(key, value)| (key.to_owned(), attribute_to_json(value))).collect())
+        }
+        AttributeValue::Ss(values) => Value::Array(values.iter().map(|value| Value::String(value.to_owned())).collect()),
+        _ => Value::Null
+    }
+}
+
+fn extract_s_or_default(s: Option<&AttributeValue>) -> String {
+    match s {
+        Some(AttributeValue::S(string)) => string.to_owned(),
+        _ => String::new()
+    }
+}
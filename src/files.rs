@@ -0,0 +1,85 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::models::Step;
+use crate::serializer;
+
+const JSON: &str = "json";
+const YAML: &[&str] = &["yaml", "yml"];
+
+/// Loads the steps of every file in order. A file holds either a single step or an array of steps.
+pub fn load_files(paths: &[PathBuf]) -> Result<Vec<Step>, Box<dyn Error>> {
+    let mut steps = vec![];
+    for path in paths {
+        steps.extend(load_file(path)?);
+    }
+    Ok(steps)
+}
+
+/// Loads every JSON and YAML file of the directory, sorted by file name.
+pub fn load_dir(dir: &Path) -> Result<Vec<Step>, Box<dyn Error>> {
+    load_files(&step_files(dir)?)
+}
+
+/// JSON and YAML files of the directory, sorted by file name.
+pub fn step_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))? {
+        let path = entry?.path();
+        if path.is_file() && is_step_file(&path) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Reads the raw step objects of a file without deserializing them into steps.
+pub fn load_values(path: &Path) -> Result<Vec<Value>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let value = match is_yaml(path) {
+        true => serde_yaml::from_str::<Value>(&content).map_err(|e| format!("{}: {}", path.display(), e))?,
+        false => serde_json::from_str::<Value>(&content).map_err(|e| format!("{}: {}", path.display(), e))?,
+    };
+    match value {
+        Value::Array(values) => Ok(values),
+        value => Ok(vec![value]),
+    }
+}
+
+pub fn load_file(path: &Path) -> Result<Vec<Step>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let steps = match is_yaml(path) {
+        true => serializer::run_parse_yaml(&content).map_err(|e| format!("{}: {}", path.display(), e))?,
+        false => match content.trim_start().starts_with('[') {
+            true => serializer::run_parse(&content).map_err(|e| format!("{}: {}", path.display(), e))?,
+            false => {
+                let step = serde_json::from_str::<Step>(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+                vec![step]
+            }
+        },
+    };
+    Ok(steps)
+}
+
+fn is_step_file(path: &Path) -> bool {
+    is_yaml(path) || extension(path).as_deref() == Some(JSON)
+}
+
+fn is_yaml(path: &Path) -> bool {
+    match extension(path) {
+        None => false,
+        Some(extension) => YAML.contains(&extension.as_str()),
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+}
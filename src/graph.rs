@@ -0,0 +1,357 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::history::{EventType, HistoryEvent};
+use crate::models::{Step, StepDefinition};
+use crate::validate;
+
+/// Node shape of a step, chosen by the kind of its definition.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Shape {
+    Convert,
+    Parse,
+    Http,
+    Missing,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TraceStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// What happened to one step during an execution, summed over its attempts.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StepTrace {
+    pub status: TraceStatus,
+    pub attempts: u32,
+    pub duration_ms: u64,
+}
+
+/// Path of an execution rebuilt from its history events.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Trace {
+    /// Step names in the order they were first started.
+    pub visited: Vec<String>,
+    pub steps: HashMap<String, StepTrace>,
+}
+
+impl Trace {
+    pub fn from_events(events: &[HistoryEvent]) -> Self {
+        let mut trace = Trace::default();
+        for event in events {
+            let name = match &event.step_name {
+                None => continue,
+                Some(name) => name,
+            };
+            if !trace.steps.contains_key(name) {
+                trace.visited.push(name.clone());
+            }
+            let step = trace.steps.entry(name.clone()).or_insert(StepTrace {
+                status: TraceStatus::Running,
+                attempts: 0,
+                duration_ms: 0,
+            });
+            match event.event_type {
+                EventType::StepStarted => {
+                    step.status = TraceStatus::Running;
+                    step.attempts += 1;
+                }
+                EventType::StepSucceeded => step.status = TraceStatus::Succeeded,
+                EventType::StepFailed => step.status = TraceStatus::Failed,
+                _ => {}
+            }
+            step.duration_ms += event.duration_ms.unwrap_or(0);
+        }
+        trace
+    }
+
+    /// `true` when the execution went from `from` straight to `to`.
+    fn took(&self, from: &str, to: &str) -> bool {
+        self.visited.windows(2).any(|pair| pair[0] == from && pair[1] == to)
+    }
+}
+
+/// Drawable view of a workflow. The first step is the start step, like for `StateMachine`.
+#[derive(Debug)]
+pub struct Graph<'a> {
+    steps: Vec<&'a Step>,
+    by_name: HashMap<&'a str, &'a Step>,
+    missing: Vec<&'a str>,
+    trace: Option<Trace>,
+}
+
+impl<'a> Graph<'a> {
+    pub fn new(steps: &'a [Step]) -> Self {
+        // Like the state machine, the last definition of a duplicate name wins. It is drawn where the name first
+        // appears, so the start step stays first.
+        let by_name: HashMap<&str, &Step> = steps.iter().map(|step| (step.name.as_str(), step)).collect();
+        let mut seen = HashSet::new();
+        let steps: Vec<&Step> = steps
+            .iter()
+            .filter(|step| seen.insert(step.name.as_str()))
+            .map(|step| by_name[step.name.as_str()])
+            .collect();
+
+        let mut missing = vec![];
+        for step in &steps {
+            if let Some(next) = validate::next_name(step) {
+                if !by_name.contains_key(next) && !missing.contains(&next) {
+                    missing.push(next);
+                }
+            }
+        }
+
+        Graph {
+            steps,
+            by_name,
+            missing,
+            trace: None,
+        }
+    }
+
+    /// Overlays an execution: visited steps and transitions are coloured, labels show durations and retries.
+    pub fn with_trace(mut self, trace: Trace) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph workflow {{").unwrap();
+        writeln!(dot, "    rankdir=TB;").unwrap();
+        writeln!(dot, "    node [fontname=\"Helvetica\"];").unwrap();
+        writeln!(dot, "    __start [shape=circle, label=\"\", style=filled, fillcolor=black, width=0.25];").unwrap();
+        writeln!(dot, "    __end [shape=doublecircle, label=\"\", style=filled, fillcolor=black, width=0.2];").unwrap();
+
+        for step in &self.steps {
+            let shape = shape(&step.definition);
+            let style = match self.step_trace(step) {
+                None if self.trace.is_some() => ", color=gray, fontcolor=gray",
+                None => "",
+                Some(step_trace) => match step_trace.status {
+                    TraceStatus::Succeeded => ", style=filled, fillcolor=palegreen",
+                    TraceStatus::Failed => ", style=filled, fillcolor=salmon, color=red",
+                    TraceStatus::Running => ", style=filled, fillcolor=lightyellow",
+                },
+            };
+            writeln!(dot, "    \"{}\" [shape={}, label=\"{}\"{}];", escape_dot(&step.name), dot_shape(shape), escape_dot(&self.label(step)), style).unwrap();
+        }
+        for name in &self.missing {
+            writeln!(
+                dot,
+                "    \"{}\" [shape={}, label=\"{}\\n(missing)\", style=dashed, color=red, fontcolor=red];",
+                escape_dot(name),
+                dot_shape(Shape::Missing),
+                escape_dot(name),
+            ).unwrap();
+        }
+
+        if let Some(start) = self.steps.first() {
+            let style = match self.step_trace(start) {
+                Some(_) => " [color=blue, penwidth=2]",
+                None => "",
+            };
+            writeln!(dot, "    __start -> \"{}\"{};", escape_dot(&start.name), style).unwrap();
+        }
+        for step in &self.steps {
+            match self.target(step) {
+                Target::End => {
+                    let style = match self.step_trace(step).map(|step_trace| step_trace.status) {
+                        Some(TraceStatus::Succeeded) => " [color=blue, penwidth=2]",
+                        _ => "",
+                    };
+                    writeln!(dot, "    \"{}\" -> __end{};", escape_dot(&step.name), style).unwrap();
+                }
+                Target::Step(next) => {
+                    let style = match self.took(step, next) {
+                        true => ", color=blue, fontcolor=blue, penwidth=2",
+                        false => "",
+                    };
+                    writeln!(dot, "    \"{}\" -> \"{}\" [label=\"next\"{}];", escape_dot(&step.name), escape_dot(next), style).unwrap();
+                }
+                Target::Missing(next) => {
+                    writeln!(dot, "    \"{}\" -> \"{}\" [label=\"next\", style=dashed, color=red, fontcolor=red];", escape_dot(&step.name), escape_dot(next)).unwrap();
+                }
+            }
+        }
+
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        // Step names may contain characters mermaid doesn't accept in ids, so nodes get positional ids.
+        let mut ids: HashMap<&str, String> = HashMap::new();
+        for (i, step) in self.steps.iter().enumerate() {
+            ids.insert(step.name.as_str(), format!("s{}", i));
+        }
+        for (i, name) in self.missing.iter().enumerate() {
+            ids.insert(name, format!("m{}", i));
+        }
+
+        let mut mermaid = String::new();
+        writeln!(mermaid, "flowchart TD").unwrap();
+        writeln!(mermaid, "    start((start))").unwrap();
+        writeln!(mermaid, "    end_((end))").unwrap();
+
+        for step in &self.steps {
+            let id = &ids[step.name.as_str()];
+            let (open, close) = mermaid_shape(shape(&step.definition));
+            writeln!(mermaid, "    {}{}\"{}\"{}", id, open, escape_mermaid(&self.label(step)), close).unwrap();
+            let class = match self.step_trace(step) {
+                None if self.trace.is_some() => Some("skipped"),
+                None => None,
+                Some(step_trace) => match step_trace.status {
+                    TraceStatus::Succeeded => Some("succeeded"),
+                    TraceStatus::Failed => Some("failed"),
+                    TraceStatus::Running => Some("running"),
+                },
+            };
+            if let Some(class) = class {
+                writeln!(mermaid, "    class {} {}", id, class).unwrap();
+            }
+        }
+        for name in &self.missing {
+            let (open, close) = mermaid_shape(Shape::Missing);
+            writeln!(mermaid, "    {}{}\"{} (missing)\"{}", ids[name], open, escape_mermaid(name), close).unwrap();
+            writeln!(mermaid, "    class {} missing", ids[name]).unwrap();
+        }
+
+        // Mermaid styles links by their position, so the taken transitions are collected while writing them.
+        let mut taken = vec![];
+        let mut link = 0;
+        if let Some(start) = self.steps.first() {
+            writeln!(mermaid, "    start --> {}", ids[start.name.as_str()]).unwrap();
+            if self.step_trace(start).is_some() {
+                taken.push(link);
+            }
+            link += 1;
+        }
+        for step in &self.steps {
+            let id = &ids[step.name.as_str()];
+            match self.target(step) {
+                Target::End => {
+                    writeln!(mermaid, "    {} --> end_", id).unwrap();
+                    if self.step_trace(step).map(|step_trace| step_trace.status) == Some(TraceStatus::Succeeded) {
+                        taken.push(link);
+                    }
+                }
+                Target::Step(next) => {
+                    writeln!(mermaid, "    {} -->|next| {}", id, ids[next]).unwrap();
+                    if self.took(step, next) {
+                        taken.push(link);
+                    }
+                }
+                Target::Missing(next) => {
+                    writeln!(mermaid, "    {} -.->|next| {}", id, ids[next]).unwrap();
+                }
+            }
+            link += 1;
+        }
+
+        if !self.missing.is_empty() {
+            writeln!(mermaid, "    classDef missing stroke:#d00,stroke-dasharray:5 5,color:#d00").unwrap();
+        }
+        if self.trace.is_some() {
+            writeln!(mermaid, "    classDef succeeded fill:#cfc,stroke:#393").unwrap();
+            writeln!(mermaid, "    classDef failed fill:#f99,stroke:#d00").unwrap();
+            writeln!(mermaid, "    classDef running fill:#ffc,stroke:#cc0").unwrap();
+            writeln!(mermaid, "    classDef skipped color:#999,stroke:#999").unwrap();
+        }
+        for link in taken {
+            writeln!(mermaid, "    linkStyle {} stroke:#00c,stroke-width:3px", link).unwrap();
+        }
+        mermaid
+    }
+
+    fn step_trace(&self, step: &Step) -> Option<&StepTrace> {
+        self.trace.as_ref().and_then(|trace| trace.steps.get(&step.name))
+    }
+
+    fn took(&self, from: &Step, to: &str) -> bool {
+        match &self.trace {
+            None => false,
+            Some(trace) => trace.took(&from.name, to),
+        }
+    }
+
+    fn label(&self, step: &Step) -> String {
+        let label = label(step);
+        match self.step_trace(step) {
+            None => label,
+            Some(step_trace) => match step_trace.attempts {
+                0 | 1 => format!("{}\n{} ms", label, step_trace.duration_ms),
+                2 => format!("{}\n{} ms, 1 retry", label, step_trace.duration_ms),
+                attempts => format!("{}\n{} ms, {} retries", label, step_trace.duration_ms, attempts - 1),
+            },
+        }
+    }
+
+    fn target(&self, step: &'a Step) -> Target<'a> {
+        match validate::next_name(step) {
+            None => Target::End,
+            // `get_next` treats a step pointing at itself as the end of the workflow.
+            Some(next) if next == step.name => Target::End,
+            Some(next) => match self.by_name.get(next) {
+                None => Target::Missing(next),
+                Some(next) => Target::Step(next.name.as_str()),
+            },
+        }
+    }
+}
+
+enum Target<'a> {
+    End,
+    Step(&'a str),
+    Missing(&'a str),
+}
+
+fn shape(definition: &StepDefinition) -> Shape {
+    match definition {
+        StepDefinition::Convert(_) => Shape::Convert,
+        StepDefinition::Parse(_) => Shape::Parse,
+        StepDefinition::Http(_) => Shape::Http,
+    }
+}
+
+fn label(step: &Step) -> String {
+    let kind = match &step.definition {
+        StepDefinition::Convert(_) => "convert".to_owned(),
+        StepDefinition::Parse(_) => "parse".to_owned(),
+        StepDefinition::Http(http) => format!("http {}", http.http_type.method()),
+    };
+    format!("{}\n({})", step.name, kind)
+}
+
+fn dot_shape(shape: Shape) -> &'static str {
+    match shape {
+        Shape::Convert => "box",
+        Shape::Parse => "parallelogram",
+        Shape::Http => "ellipse",
+        Shape::Missing => "octagon",
+    }
+}
+
+fn mermaid_shape(shape: Shape) -> (&'static str, &'static str) {
+    match shape {
+        Shape::Convert => ("[", "]"),
+        Shape::Parse => ("[/", "/]"),
+        Shape::Http => ("([", "])"),
+        Shape::Missing => ("{{", "}}"),
+    }
+}
+
+fn escape_dot(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_mermaid(value: &str) -> String {
+    value
+        .replace('"', "#quot;")
+        .replace('\n', "<br/>")
+}
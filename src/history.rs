@@ -0,0 +1,438 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use aws_sdk_dynamodb::Client;
+use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::runtime::Handle;
+
+use crate::models::Step;
+
+pub const HISTORY_TABLE: &str = "ParseStepHistory";
+/// Global secondary index on `workflow_id` used to list the executions of a workflow.
+pub const WORKFLOW_INDEX: &str = "workflow_id-index";
+
+const EXECUTION_ID_KEY: &str = "execution_id";
+const SEQUENCE_KEY: &str = "sequence";
+const WORKFLOW_ID_KEY: &str = "workflow_id";
+const EVENT_TYPE_KEY: &str = "event_type";
+const STEP_NAME_KEY: &str = "step_name";
+const STEP_UUID_KEY: &str = "step_uuid";
+const TIMESTAMP_KEY: &str = "timestamp";
+const ATTEMPT_KEY: &str = "attempt";
+const DURATION_KEY: &str = "duration_ms";
+const OUTPUT_KEY: &str = "output";
+const ERROR_KEY: &str = "error";
+
+/// Outputs longer than this are cut before they are written to the history.
+const OUTPUT_SUMMARY_LEN: usize = 256;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+pub enum EventType {
+    ExecutionStarted,
+    ExecutionResumed,
+    ExecutionSucceeded,
+    ExecutionFailed,
+    StepStarted,
+    StepSucceeded,
+    StepFailed,
+    StepRetried,
+    HttpCacheHit,
+    HttpCacheMiss,
+}
+
+impl EventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventType::ExecutionStarted => "ExecutionStarted",
+            EventType::ExecutionResumed => "ExecutionResumed",
+            EventType::ExecutionSucceeded => "ExecutionSucceeded",
+            EventType::ExecutionFailed => "ExecutionFailed",
+            EventType::StepStarted => "StepStarted",
+            EventType::StepSucceeded => "StepSucceeded",
+            EventType::StepFailed => "StepFailed",
+            EventType::StepRetried => "StepRetried",
+            EventType::HttpCacheHit => "HttpCacheHit",
+            EventType::HttpCacheMiss => "HttpCacheMiss",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ExecutionStarted" => Some(EventType::ExecutionStarted),
+            "ExecutionResumed" => Some(EventType::ExecutionResumed),
+            "ExecutionSucceeded" => Some(EventType::ExecutionSucceeded),
+            "ExecutionFailed" => Some(EventType::ExecutionFailed),
+            "StepStarted" => Some(EventType::StepStarted),
+            "StepSucceeded" => Some(EventType::StepSucceeded),
+            "StepFailed" => Some(EventType::StepFailed),
+            "StepRetried" => Some(EventType::StepRetried),
+            "HttpCacheHit" => Some(EventType::HttpCacheHit),
+            "HttpCacheMiss" => Some(EventType::HttpCacheMiss),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct HistoryEvent {
+    #[serde(rename = "executionId")]
+    pub execution_id: String,
+    pub sequence: u32,
+    #[serde(rename = "workflowId")]
+    pub workflow_id: Option<String>,
+    #[serde(rename = "eventType")]
+    pub event_type: EventType,
+    #[serde(rename = "stepName")]
+    pub step_name: Option<String>,
+    #[serde(rename = "stepUuid")]
+    pub step_uuid: Option<String>,
+    pub timestamp: String,
+    pub attempt: Option<u32>,
+    #[serde(rename = "durationMs")]
+    pub duration_ms: Option<u64>,
+    pub output: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Destination for the events of an execution.
+pub trait ExecutionHistory: fmt::Debug {
+    fn record(&self, event: &HistoryEvent) -> Result<(), Box<dyn Error>>;
+    /// Sequence after the last recorded event of the execution, 0 when it has none.
+    fn next_sequence(&self, execution_id: &str) -> Result<u32, Box<dyn Error>>;
+}
+
+/// Numbers the events of one `StateMachine` run and forwards them to the configured history.
+pub struct Execution<'a> {
+    pub id: String,
+    workflow_id: Option<String>,
+    sequence: Cell<u32>,
+    history: Option<&'a dyn ExecutionHistory>,
+}
+
+impl<'a> Execution<'a> {
+    /// `sequence` numbers the first event, a resumed execution continues after the events already recorded.
+    pub fn new(id: String, workflow_id: Option<String>, sequence: u32, history: Option<&'a dyn ExecutionHistory>) -> Self {
+        Execution {
+            id,
+            workflow_id,
+            sequence: Cell::new(sequence),
+            history,
+        }
+    }
+
+    pub fn execution_event(&self, event_type: EventType, duration: Option<Duration>) {
+        self.record(event_type, None, None, duration, None, None);
+    }
+
+    pub fn step_event(
+        &self,
+        event_type: EventType,
+        step: &Step,
+        attempt: u32,
+        duration: Option<Duration>,
+        output: Option<&Value>,
+        error: Option<&dyn Error>,
+    ) {
+        self.record(event_type, Some(step), Some(attempt), duration, output, error);
+    }
+
+    fn record(
+        &self,
+        event_type: EventType,
+        step: Option<&Step>,
+        attempt: Option<u32>,
+        duration: Option<Duration>,
+        output: Option<&Value>,
+        error: Option<&dyn Error>,
+    ) {
+        let history = match self.history {
+            None => return,
+            Some(history) => history,
+        };
+
+        let sequence = self.sequence.get();
+        self.sequence.set(sequence + 1);
+
+        let event = HistoryEvent {
+            execution_id: self.id.clone(),
+            sequence,
+            workflow_id: self.workflow_id.clone(),
+            event_type,
+            step_name: step.map(|step| step.name.clone()),
+            step_uuid: step.map(|step| step.uuid.clone()),
+            timestamp: Utc::now().to_rfc3339(),
+            attempt,
+            duration_ms: duration.map(|duration| duration.as_millis() as u64),
+            output: output.map(summarize),
+            error: error.map(|error| error.to_string()),
+        };
+
+        if let Err(e) = history.record(&event) {
+            println!("> [{}] failed to record {} event: {}", self.id, event_type, e);
+        }
+    }
+}
+
+fn summarize(output: &Value) -> String {
+    let mut summary = output.to_string();
+    if summary.len() > OUTPUT_SUMMARY_LEN {
+        let mut end = OUTPUT_SUMMARY_LEN;
+        while !summary.is_char_boundary(end) {
+            end -= 1;
+        }
+        summary.truncate(end);
+        summary.push('…');
+    }
+    summary
+}
+
+/// Stores every event as an item keyed by `execution_id` (partition) and `sequence` (sort).
+#[derive(Debug)]
+pub struct DynamoHistory {
+    client: Client,
+    table: String,
+    handle: Handle,
+}
+
+impl DynamoHistory {
+    /// Must be created from within the tokio runtime that will serve the writes.
+    pub fn new(client: Client, table: &str) -> Self {
+        DynamoHistory {
+            client,
+            table: table.to_owned(),
+            handle: Handle::current(),
+        }
+    }
+
+    /// Returns the `ExecutionStarted` event of every execution of the workflow.
+    pub async fn list_executions(&self, workflow_id: &str) -> Result<Vec<HistoryEvent>, Box<dyn Error>> {
+        let mut executions = vec![];
+        let mut start_key = None;
+
+        loop {
+            let resp = self.client
+                .query()
+                .table_name(&self.table)
+                .index_name(WORKFLOW_INDEX)
+                .key_condition_expression("#wf = :wf")
+                .filter_expression("#et = :et")
+                .expression_attribute_names("#wf", WORKFLOW_ID_KEY)
+                .expression_attribute_names("#et", EVENT_TYPE_KEY)
+                .expression_attribute_values(":wf", AttributeValue::S(workflow_id.to_owned()))
+                .expression_attribute_values(":et", AttributeValue::S(EventType::ExecutionStarted.to_string()))
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            for item in resp.items.unwrap_or_default() {
+                executions.extend(item_to_event(&item)?);
+            }
+
+            match resp.last_evaluated_key {
+                None => break,
+                Some(key) => start_key = Some(key),
+            }
+        }
+
+        executions.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        Ok(executions)
+    }
+
+    /// Returns the event log of one execution ordered by sequence.
+    pub async fn events(&self, execution_id: &str) -> Result<Vec<HistoryEvent>, Box<dyn Error>> {
+        let mut events = vec![];
+        let mut start_key = None;
+
+        loop {
+            let resp = self.client
+                .query()
+                .table_name(&self.table)
+                .key_condition_expression("#id = :id")
+                .expression_attribute_names("#id", EXECUTION_ID_KEY)
+                .expression_attribute_values(":id", AttributeValue::S(execution_id.to_owned()))
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            for item in resp.items.unwrap_or_default() {
+                events.extend(item_to_event(&item)?);
+            }
+
+            match resp.last_evaluated_key {
+                None => break,
+                Some(key) => start_key = Some(key),
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+impl ExecutionHistory for DynamoHistory {
+    fn record(&self, event: &HistoryEvent) -> Result<(), Box<dyn Error>> {
+        let request = self.client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(event_to_item(event)))
+            .send();
+
+        tokio::task::block_in_place(|| match self.handle.block_on(request) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.into()),
+        })
+    }
+
+    fn next_sequence(&self, execution_id: &str) -> Result<u32, Box<dyn Error>> {
+        // The sort key orders the events, so the first one read backwards is the last recorded.
+        let request = self.client
+            .query()
+            .table_name(&self.table)
+            .key_condition_expression("#id = :id")
+            .expression_attribute_names("#id", EXECUTION_ID_KEY)
+            .expression_attribute_values(":id", AttributeValue::S(execution_id.to_owned()))
+            .scan_index_forward(false)
+            .limit(1)
+            .send();
+
+        let items = tokio::task::block_in_place(|| match self.handle.block_on(request) {
+            Ok(output) => Ok(output.items.unwrap_or_default()),
+            Err(e) => Err(Box::<dyn Error>::from(e)),
+        })?;
+
+        match items.first().map(|item| item.get(SEQUENCE_KEY)) {
+            None => Ok(0),
+            Some(Some(AttributeValue::N(sequence))) => match sequence.parse::<u32>() {
+                Ok(sequence) => sequence.checked_add(1).ok_or_else(|| format!("execution {} has no sequence numbers left", execution_id).into()),
+                Err(_) => Err(format!("sequence `{}` of execution {} is not a valid number", sequence, execution_id).into()),
+            },
+            Some(_) => Err(format!("an event of execution {} has no sequence", execution_id).into()),
+        }
+    }
+}
+
+fn event_to_item(event: &HistoryEvent) -> HashMap<String, AttributeValue> {
+    let mut item = HashMap::new();
+    item.insert(EXECUTION_ID_KEY.to_owned(), AttributeValue::S(event.execution_id.clone()));
+    item.insert(SEQUENCE_KEY.to_owned(), AttributeValue::N(event.sequence.to_string()));
+    item.insert(EVENT_TYPE_KEY.to_owned(), AttributeValue::S(event.event_type.to_string()));
+    item.insert(TIMESTAMP_KEY.to_owned(), AttributeValue::S(event.timestamp.clone()));
+    if let Some(workflow_id) = &event.workflow_id {
+        item.insert(WORKFLOW_ID_KEY.to_owned(), AttributeValue::S(workflow_id.clone()));
+    }
+    if let Some(step_name) = &event.step_name {
+        item.insert(STEP_NAME_KEY.to_owned(), AttributeValue::S(step_name.clone()));
+    }
+    if let Some(step_uuid) = &event.step_uuid {
+        item.insert(STEP_UUID_KEY.to_owned(), AttributeValue::S(step_uuid.clone()));
+    }
+    if let Some(attempt) = event.attempt {
+        item.insert(ATTEMPT_KEY.to_owned(), AttributeValue::N(attempt.to_string()));
+    }
+    if let Some(duration_ms) = event.duration_ms {
+        item.insert(DURATION_KEY.to_owned(), AttributeValue::N(duration_ms.to_string()));
+    }
+    if let Some(output) = &event.output {
+        item.insert(OUTPUT_KEY.to_owned(), AttributeValue::S(output.clone()));
+    }
+    if let Some(error) = &event.error {
+        item.insert(ERROR_KEY.to_owned(), AttributeValue::S(error.clone()));
+    }
+    item
+}
+
+/// Items missing a required attribute are skipped, numbers too large for their field are an error.
+fn item_to_event(item: &HashMap<String, AttributeValue>) -> Result<Option<HistoryEvent>, Box<dyn Error>> {
+    let s = |key: &str| match item.get(key) {
+        Some(AttributeValue::S(value)) => Some(value.to_owned()),
+        _ => None
+    };
+    let n = |key: &str| match item.get(key) {
+        Some(AttributeValue::N(value)) => value.parse::<u64>().ok(),
+        _ => None
+    };
+
+    let (Some(execution_id), Some(sequence), Some(event_type), Some(timestamp)) =
+        (s(EXECUTION_ID_KEY), n(SEQUENCE_KEY), s(EVENT_TYPE_KEY).as_deref().and_then(EventType::parse), s(TIMESTAMP_KEY)) else {
+        return Ok(None);
+    };
+    let small = |key: &str, value: u64| u32::try_from(value)
+        .map_err(|_| format!("{} `{}` of execution {} is larger than {}", key, value, execution_id, u32::MAX));
+
+    Ok(Some(HistoryEvent {
+        sequence: small(SEQUENCE_KEY, sequence)?,
+        workflow_id: s(WORKFLOW_ID_KEY),
+        event_type,
+        step_name: s(STEP_NAME_KEY),
+        step_uuid: s(STEP_UUID_KEY),
+        timestamp,
+        attempt: n(ATTEMPT_KEY).map(|attempt| small(ATTEMPT_KEY, attempt)).transpose()?,
+        duration_ms: n(DURATION_KEY),
+        output: s(OUTPUT_KEY),
+        error: s(ERROR_KEY),
+        execution_id,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> HistoryEvent {
+        HistoryEvent {
+            execution_id: "3f1c".to_owned(),
+            sequence: 7,
+            workflow_id: Some("scraper".to_owned()),
+            event_type: EventType::StepRetried,
+            step_name: Some("fetch".to_owned()),
+            step_uuid: Some("07124d66-f345-4c0b-90cc-5d45efbb0891".to_owned()),
+            timestamp: "2022-02-01T10:00:00+00:00".to_owned(),
+            attempt: Some(2),
+            duration_ms: Some(1_500),
+            output: Some("{\"status\":503}".to_owned()),
+            error: Some("GET https://example.com returned 503".to_owned()),
+        }
+    }
+
+    #[test]
+    fn event_round_trips_through_an_item() {
+        let event = event();
+        assert_eq!(item_to_event(&event_to_item(&event)).unwrap(), Some(event));
+    }
+
+    #[test]
+    fn optional_fields_are_left_out_of_the_item() {
+        let event = HistoryEvent {
+            workflow_id: None,
+            event_type: EventType::ExecutionStarted,
+            step_name: None,
+            step_uuid: None,
+            attempt: None,
+            duration_ms: None,
+            output: None,
+            error: None,
+            ..event()
+        };
+        let item = event_to_item(&event);
+        assert_eq!(item.len(), 4);
+        assert_eq!(item_to_event(&item).unwrap(), Some(event));
+    }
+
+    #[test]
+    fn numbers_too_large_for_their_field_are_an_error() {
+        let mut item = event_to_item(&event());
+        item.insert(ATTEMPT_KEY.to_owned(), AttributeValue::N("4294967296".to_owned()));
+        assert!(item_to_event(&item).is_err());
+    }
+}
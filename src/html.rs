@@ -0,0 +1,418 @@
+use ego_tree::NodeRef;
+use scraper::{ElementRef, Node};
+
+use crate::models::TextFormat;
+
+/// Elements whose content is never shown.
+const HIDDEN: [&str; 9] = ["head", "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object"];
+
+/// Elements that start a new paragraph.
+const PARAGRAPHS: [&str; 9] = ["p", "blockquote", "pre", "table", "ul", "ol", "dl", "figure", "hr"];
+
+/// Elements that start a new line.
+const LINES: [&str; 16] = [
+    "div", "section", "article", "header", "footer", "main", "nav", "aside", "form", "address", "figcaption", "li", "dt",
+    "dd", "tr", "fieldset",
+];
+
+pub struct TableRow<'a> {
+    pub in_head: bool,
+    pub cells: Vec<ElementRef<'a>>,
+}
+
+/// Rows of the table itself, rows of nested tables are left out.
+pub fn table_rows(table: ElementRef) -> Vec<TableRow> {
+    let mut rows = vec![];
+    for child in table.children().filter_map(ElementRef::wrap) {
+        let (in_head, section_rows) = match child.value().name() {
+            "tr" => (false, vec![child]),
+            "thead" | "tbody" | "tfoot" => (
+                child.value().name() == "thead",
+                child.children().filter_map(ElementRef::wrap).filter(|row| row.value().name() == "tr").collect(),
+            ),
+            _ => continue,
+        };
+        for row in section_rows {
+            rows.push(TableRow {
+                in_head,
+                cells: row
+                    .children()
+                    .filter_map(ElementRef::wrap)
+                    .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                    .collect(),
+            });
+        }
+    }
+    rows
+}
+
+/// Readable text of the elements, separated by blank lines. Scripts and styles are dropped, headings, links,
+/// lists and tables are kept as Markdown or as their plain text equivalent.
+pub fn render(elements: &[ElementRef], format: TextFormat) -> String {
+    let mut writer = TextWriter {
+        markdown: format == TextFormat::Markdown,
+        out: String::new(),
+        prefixes: vec![],
+        lists: vec![],
+        breaks: 0,
+        space: false,
+        glued: false,
+        line_open: false,
+        pre: 0,
+    };
+    for element in elements {
+        writer.paragraph();
+        writer.node(**element);
+    }
+    writer.out.trim_end().to_owned()
+}
+
+/// Text put in front of the lines of a list item or quote, `first` on its first line and `rest` on the others.
+struct Prefix {
+    first: String,
+    rest: String,
+    used: bool,
+}
+
+struct TextWriter {
+    markdown: bool,
+    out: String,
+    prefixes: Vec<Prefix>,
+    /// Counter of each open list, `None` for unordered ones.
+    lists: Vec<Option<usize>>,
+    /// Line breaks to write before the next text.
+    breaks: usize,
+    /// Whitespace to write before the next text.
+    space: bool,
+    /// The next text follows an opening marker like `**` without whitespace.
+    glued: bool,
+    line_open: bool,
+    /// Depth of `<pre>` elements, whitespace inside them is kept.
+    pre: usize,
+}
+
+impl TextWriter {
+    fn node(&mut self, node: NodeRef<Node>) {
+        match node.value() {
+            Node::Text(text) => match self.pre {
+                0 => self.text(text),
+                _ => self.preformatted(text),
+            },
+            Node::Element(_) => self.element(ElementRef::wrap(node).unwrap()),
+            Node::Document | Node::Fragment => self.children(node),
+            _ => {}
+        }
+    }
+
+    fn children(&mut self, node: NodeRef<Node>) {
+        for child in node.children() {
+            self.node(child);
+        }
+    }
+
+    fn element(&mut self, element: ElementRef) {
+        let name = element.value().name();
+        if HIDDEN.contains(&name) {
+            return;
+        }
+        match name {
+            "br" => self.breaks = (self.breaks + 1).min(2),
+            "hr" => {
+                self.paragraph();
+                self.write("---");
+                self.paragraph();
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.paragraph();
+                if self.markdown {
+                    let level = name[1..].parse().unwrap_or(1);
+                    self.write(&"#".repeat(level));
+                    self.space = true;
+                }
+                self.children(*element);
+                self.paragraph();
+            }
+            "ul" | "ol" => {
+                match self.lists.is_empty() {
+                    true => self.paragraph(),
+                    false => self.line(),
+                }
+                self.lists.push(match name {
+                    "ol" => Some(element.value().attr("start").and_then(|start| start.parse().ok()).unwrap_or(1)),
+                    _ => None,
+                });
+                self.children(*element);
+                self.lists.pop();
+                match self.lists.is_empty() {
+                    true => self.paragraph(),
+                    false => self.line(),
+                }
+            }
+            "li" => {
+                self.line();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(counter)) => {
+                        *counter += 1;
+                        format!("{}. ", *counter - 1)
+                    }
+                    _ => "- ".to_owned(),
+                };
+                self.prefixes.push(Prefix {
+                    rest: " ".repeat(marker.len()),
+                    first: marker,
+                    used: false,
+                });
+                self.children(*element);
+                self.prefixes.pop();
+                self.line();
+            }
+            "blockquote" if self.markdown => {
+                self.paragraph();
+                self.prefixes.push(Prefix {
+                    first: "> ".to_owned(),
+                    rest: "> ".to_owned(),
+                    used: false,
+                });
+                self.children(*element);
+                self.prefixes.pop();
+                self.paragraph();
+            }
+            "pre" => {
+                self.paragraph();
+                if self.markdown {
+                    self.write("```");
+                    self.line();
+                }
+                self.pre += 1;
+                self.children(*element);
+                self.pre -= 1;
+                // The last line break of the content is part of the closing fence.
+                let end = self.out.trim_end_matches('\n').len();
+                self.out.truncate(end);
+                if self.markdown {
+                    self.line();
+                    self.write("```");
+                }
+                self.paragraph();
+            }
+            "table" => {
+                self.paragraph();
+                for (i, row) in table_rows(element).iter().enumerate() {
+                    self.line();
+                    self.row(&row.cells);
+                    if self.markdown && i == 0 {
+                        self.line();
+                        self.write(&format!("|{}", " --- |".repeat(row.cells.len())));
+                    }
+                }
+                self.paragraph();
+            }
+            "a" => {
+                let href = element.value().attr("href").unwrap_or_default().trim();
+                let shown = !href.is_empty() && !href.starts_with('#') && !href.starts_with("javascript:");
+                match (shown, self.markdown) {
+                    (false, _) => self.children(*element),
+                    (true, true) => {
+                        self.write("[");
+                        self.glued = true;
+                        self.children(*element);
+                        self.out.push_str(&format!("]({})", href));
+                    }
+                    (true, false) => {
+                        self.children(*element);
+                        self.space = true;
+                        self.write(&format!("({})", href));
+                    }
+                }
+            }
+            "img" => {
+                let alt = element.value().attr("alt").unwrap_or_default().trim();
+                match self.markdown {
+                    true => self.write(&format!("![{}]({})", alt, element.value().attr("src").unwrap_or_default())),
+                    false if !alt.is_empty() => self.write(alt),
+                    false => {}
+                }
+            }
+            "strong" | "b" | "em" | "i" | "code" if self.markdown && self.pre == 0 => {
+                let marker = match name {
+                    "strong" | "b" => "**",
+                    "em" | "i" => "*",
+                    _ => "`",
+                };
+                match element.text().all(|text| text.trim().is_empty()) {
+                    true => self.children(*element),
+                    false => {
+                        self.write(marker);
+                        self.glued = true;
+                        self.children(*element);
+                        self.out.push_str(marker);
+                    }
+                }
+            }
+            _ if PARAGRAPHS.contains(&name) => {
+                self.paragraph();
+                self.children(*element);
+                self.paragraph();
+            }
+            _ if LINES.contains(&name) => {
+                self.line();
+                self.children(*element);
+                self.line();
+            }
+            _ => self.children(*element),
+        }
+    }
+
+    /// Cells of a table row separated by `|`, Markdown rows also start and end with one.
+    fn row(&mut self, cells: &[ElementRef]) {
+        if self.markdown {
+            self.write("|");
+        }
+        for (i, cell) in cells.iter().enumerate() {
+            if i > 0 && !self.markdown {
+                self.space = true;
+                self.write("|");
+            }
+            self.space = true;
+            self.children(**cell);
+            if self.markdown {
+                self.space = true;
+                self.write("|");
+            }
+        }
+    }
+
+    /// Text with its whitespace collapsed.
+    fn text(&mut self, text: &str) {
+        if text.starts_with(char::is_whitespace) {
+            self.space = true;
+        }
+        for (i, word) in text.split_whitespace().enumerate() {
+            if i > 0 {
+                self.space = true;
+            }
+            self.write(word);
+        }
+        if text.ends_with(char::is_whitespace) {
+            self.space = true;
+        }
+    }
+
+    fn preformatted(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.breaks = self.breaks.max(1);
+                self.line_open = false;
+            }
+            self.space = false;
+            self.write(line);
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        if !self.line_open || self.breaks > 0 {
+            if !self.out.is_empty() {
+                self.out.push('\n');
+                // Blank lines inside a quote or list item keep its prefix, without trailing whitespace.
+                for _ in 1..self.breaks {
+                    let rest: String = self.prefixes.iter().filter(|prefix| prefix.used).map(|prefix| prefix.rest.as_str()).collect();
+                    self.out.push_str(rest.trim_end());
+                    self.out.push('\n');
+                }
+            }
+            for prefix in self.prefixes.iter_mut() {
+                match prefix.used {
+                    true => self.out.push_str(&prefix.rest),
+                    false => self.out.push_str(&prefix.first),
+                }
+                prefix.used = true;
+            }
+            self.line_open = true;
+        } else if self.space && !self.glued {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+        self.breaks = 0;
+        self.space = false;
+        self.glued = false;
+    }
+
+    fn line(&mut self) {
+        self.break_lines(1);
+    }
+
+    fn paragraph(&mut self) {
+        self.break_lines(2);
+    }
+
+    fn break_lines(&mut self, breaks: usize) {
+        // The first block of a list item or quote starts on the line of its marker.
+        let starting = self.prefixes.last().is_some_and(|prefix| !prefix.used);
+        if self.line_open && !starting {
+            self.breaks = self.breaks.max(breaks);
+        }
+        self.space = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::Html;
+
+    use super::*;
+
+    fn text(html: &str) -> String {
+        let document = Html::parse_document(html);
+        render(&[document.root_element()], TextFormat::Text)
+    }
+
+    fn markdown(html: &str) -> String {
+        let document = Html::parse_document(html);
+        render(&[document.root_element()], TextFormat::Markdown)
+    }
+
+    const PAGE: &str = "<html><head><title>Page</title><style>p { color: red }</style></head>\
+        <body><script>alert(1)</script><h1>Fruits</h1><p>Fresh <b>apples</b> and\n   <a href=\"/pears\">pears</a>.</p>\
+        <noscript>enable js</noscript></body></html>";
+
+    #[test]
+    fn drops_head_scripts_and_styles() {
+        assert_eq!(text(PAGE), "Fruits\n\nFresh apples and pears (/pears).");
+        assert_eq!(markdown(PAGE), "# Fruits\n\nFresh **apples** and [pears](/pears).");
+    }
+
+    #[test]
+    fn headings_keep_their_level_in_markdown() {
+        let html = "<h2>Prices</h2><p>per kilo</p><h3>Apples</h3>";
+        assert_eq!(text(html), "Prices\n\nper kilo\n\nApples");
+        assert_eq!(markdown(html), "## Prices\n\nper kilo\n\n### Apples");
+    }
+
+    #[test]
+    fn links_without_a_target_are_plain_text() {
+        let html = "<p><a href=\"#top\">top</a> <a>none</a> <a href=\"javascript:void(0)\">js</a> <a href=\"https://example.com\">site</a></p>";
+        assert_eq!(text(html), "top none js site (https://example.com)");
+        assert_eq!(markdown(html), "top none js [site](https://example.com)");
+    }
+
+    #[test]
+    fn nested_lists_are_indented_under_their_item() {
+        let html = "<ul><li>fruit<ol start=\"3\"><li>apple</li><li>pear</li></ol></li><li>nuts</li></ul>";
+        assert_eq!(text(html), "- fruit\n  3. apple\n  4. pear\n- nuts");
+        assert_eq!(markdown(html), "- fruit\n  3. apple\n  4. pear\n- nuts");
+    }
+
+    #[test]
+    fn pre_keeps_its_whitespace() {
+        let html = "<p>run</p><pre>cargo  run\n  --release\n</pre>";
+        assert_eq!(text(html), "run\n\ncargo  run\n  --release");
+        assert_eq!(markdown(html), "run\n\n```\ncargo  run\n  --release\n```");
+    }
+
+    #[test]
+    fn tables_are_rows_of_cells() {
+        let html = "<table><tr><th>Fruit</th><th>Price</th></tr><tr><td>Apple</td><td>1</td></tr></table>";
+        assert_eq!(text(html), "Fruit | Price\nApple | 1");
+        assert_eq!(markdown(html), "| Fruit | Price |\n| --- | --- |\n| Apple | 1 |");
+    }
+}
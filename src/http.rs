@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::cache::CacheStatus;
+use crate::context::ExecutionContext;
+use crate::models::{HttpAuth, HttpBody, HttpDefinition, HttpExpect, HttpCache, HttpExtract, HttpLimits, RateLimit};
+use crate::template;
+
+/// Request of an http step with every placeholder resolved.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    /// Limits of the step, the client fills in the rest.
+    pub limits: HttpLimits,
+    pub rate_limit: RateLimit,
+    pub cache: Option<HttpCache>,
+}
+
+impl HttpRequest {
+    pub fn build(definition: &HttpDefinition, context: &ExecutionContext) -> Result<Self, Box<dyn Error>> {
+        let http_type = &definition.http_type;
+
+        let mut headers = vec![];
+        for (name, value) in &definition.headers {
+            headers.push((name.to_owned(), template::render(value, context)?));
+        }
+        match &definition.auth {
+            None => {}
+            Some(HttpAuth::Bearer(token)) => {
+                headers.push(("Authorization".to_owned(), format!("Bearer {}", template::render(token, context)?)));
+            }
+            Some(HttpAuth::Basic { username, password }) => {
+                let credentials = format!("{}:{}", template::render(username, context)?, template::render(password, context)?);
+                headers.push(("Authorization".to_owned(), format!("Basic {}", STANDARD.encode(credentials))));
+            }
+            Some(HttpAuth::ApiKey { header, value }) => {
+                headers.push((header.to_owned(), template::render(value, context)?));
+            }
+        }
+
+        let mut query = vec![];
+        for (name, value) in &definition.query {
+            query.push((name.to_owned(), template::render(value, context)?));
+        }
+
+        let body = match http_type.body() {
+            None => None,
+            Some(body) => {
+                let (content_type, body) = render_body(body, context)?;
+                let has_content_type = headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-type"));
+                if let (Some(content_type), false) = (content_type, has_content_type) {
+                    headers.push(("Content-Type".to_owned(), content_type));
+                }
+                Some(body)
+            }
+        };
+
+        Ok(HttpRequest {
+            method: http_type.method().to_owned(),
+            url: http_type.url().to_owned(),
+            query,
+            headers,
+            body,
+            limits: definition.limits.clone(),
+            rate_limit: definition.rate_limit.clone(),
+            cache: definition.cache.clone(),
+        })
+    }
+}
+
+/// Serialized body and the content type it is sent with. Raw bodies keep whatever the headers say.
+fn render_body(body: &HttpBody, context: &ExecutionContext) -> Result<(Option<String>, String), Box<dyn Error>> {
+    match body {
+        HttpBody::Raw(body) => Ok((None, template::render(body, context)?)),
+        HttpBody::Json(body) => {
+            let body = render_json(body, context)?;
+            Ok((Some("application/json".to_owned()), serde_json::to_string(&body)?))
+        }
+        HttpBody::Form(fields) => {
+            let mut form = url::form_urlencoded::Serializer::new(String::new());
+            for (name, value) in fields {
+                form.append_pair(name, &template::render(value, context)?);
+            }
+            Ok((Some("application/x-www-form-urlencoded".to_owned()), form.finish()))
+        }
+        HttpBody::Multipart(fields) => {
+            let boundary = format!("psm-{}", Uuid::new_v4().simple());
+            let mut multipart = String::new();
+            for field in fields {
+                multipart.push_str(&format!("--{}\r\n", boundary));
+                match &field.filename {
+                    None => {
+                        multipart.push_str(&format!("Content-Disposition: form-data; name=\"{}\"\r\n", disposition_param(&field.name)?));
+                    }
+                    Some(filename) => {
+                        multipart.push_str(&format!(
+                            "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n",
+                            disposition_param(&field.name)?,
+                            disposition_param(&template::render(filename, context)?)?,
+                        ));
+                    }
+                }
+                if let Some(content_type) = &field.content_type {
+                    multipart.push_str(&format!("Content-Type: {}\r\n", content_type));
+                }
+                multipart.push_str("\r\n");
+                multipart.push_str(&template::render(&field.value, context)?);
+                multipart.push_str("\r\n");
+            }
+            multipart.push_str(&format!("--{}--\r\n", boundary));
+            Ok((Some(format!("multipart/form-data; boundary={}", boundary)), multipart))
+        }
+    }
+}
+
+/// Quoted `Content-Disposition` parameter. Quotes are percent-encoded like browsers do, a line break would end
+/// the header and start one chosen by the value, so it is an error.
+fn disposition_param(value: &str) -> Result<String, Box<dyn Error>> {
+    if value.contains(['\r', '\n']) {
+        return Err(format!("multipart name or filename `{}` contains a line break", value.escape_debug()).into());
+    }
+    Ok(value.replace('"', "%22"))
+}
+
+/// Renders the placeholders of every string in the JSON body, keys included.
+fn render_json(value: &Value, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+    match value {
+        Value::String(value) => Ok(Value::String(template::render(value, context)?)),
+        Value::Array(values) => {
+            let mut rendered = vec![];
+            for value in values {
+                rendered.push(render_json(value, context)?);
+            }
+            Ok(Value::Array(rendered))
+        }
+        Value::Object(object) => {
+            let mut rendered = Map::new();
+            for (key, value) in object {
+                rendered.insert(template::render(key, context)?, render_json(value, context)?);
+            }
+            Ok(Value::Object(rendered))
+        }
+        value => Ok(value.clone()),
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// Set when the request went through the cache.
+    pub cache: Option<CacheStatus>,
+}
+
+impl HttpResponse {
+    /// Output of the http step: `{"status": 200, "headers": {"content-type": "..."}, "body": "..."}`.
+    pub fn to_value(&self) -> Value {
+        let mut headers = Map::new();
+        for (name, value) in &self.headers {
+            headers.insert(name.to_lowercase(), Value::String(value.to_owned()));
+        }
+        let mut output = Map::new();
+        output.insert("status".to_owned(), Value::from(self.status));
+        output.insert("headers".to_owned(), Value::Object(headers));
+        output.insert("body".to_owned(), Value::String(self.body.to_owned()));
+        Value::Object(output)
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    fn json(&self) -> Result<Value, String> {
+        serde_json::from_str(&self.body).map_err(|e| format!("body is not JSON: {}", e))
+    }
+
+    /// First expectation the response doesn't meet.
+    pub fn check(&self, expect: &HttpExpect) -> Result<(), String> {
+        match expect.status.is_empty() {
+            true if self.status >= 400 => return Err(format!("returned {}", self.status)),
+            false if !expect.status.contains(&self.status) => {
+                return Err(format!("returned {}, expected one of {:?}", self.status, expect.status));
+            }
+            _ => {}
+        }
+
+        for (name, expected) in &expect.headers {
+            match (self.header(name), expected) {
+                (None, _) => return Err(format!("header `{}` is missing", name)),
+                (Some(value), Some(expected)) if !value.contains(expected.as_str()) => {
+                    return Err(format!("header `{}` is `{}`, expected it to contain `{}`", name, value, expected));
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(expected) = &expect.body_contains {
+            if !self.body.contains(expected.as_str()) {
+                return Err(format!("body doesn't contain `{}`", expected));
+            }
+        }
+        if let Some(regex) = &expect.body_matches {
+            if !regex.is_match(&self.body) {
+                return Err(format!("body doesn't match `{}`", regex));
+            }
+        }
+
+        if !expect.json.is_empty() {
+            let json = self.json()?;
+            for (pointer, expected) in &expect.json {
+                match json.pointer(pointer) {
+                    None => return Err(format!("`{}` is not in the body", pointer)),
+                    Some(value) if value != expected => {
+                        return Err(format!("`{}` is {}, expected {}", pointer, value, expected));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Output of an http step with extraction rules: `{"name": value, ...}`.
+    pub fn extract(&self, rules: &[(String, HttpExtract)]) -> Result<Value, String> {
+        let json = match rules.iter().any(|(_, rule)| matches!(rule, HttpExtract::Pointer(_))) {
+            true => Some(self.json()?),
+            false => None,
+        };
+
+        let mut output = Map::new();
+        for (name, rule) in rules {
+            let value = match (rule, &json) {
+                (HttpExtract::Pointer(pointer), Some(json)) => match json.pointer(pointer) {
+                    None => return Err(format!("cannot extract `{}`, `{}` is not in the body", name, pointer)),
+                    Some(value) => value.clone(),
+                },
+                (HttpExtract::Header(header), _) => match self.header(header) {
+                    None => return Err(format!("cannot extract `{}`, header `{}` is missing", name, header)),
+                    Some(value) => Value::String(value.to_owned()),
+                },
+                (HttpExtract::Pointer(_), None) => unreachable!("the body is parsed when there are pointer rules"),
+            };
+            output.insert(name.to_owned(), value);
+        }
+        Ok(Value::Object(output))
+    }
+}
+
+/// Sends the requests of http steps.
+pub trait HttpClient: fmt::Debug {
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Box<dyn Error>>;
+}
+
+pub const DEFAULT_CONNECT_TIMEOUT_MS: u64 = 10_000;
+pub const DEFAULT_READ_TIMEOUT_MS: u64 = 30_000;
+pub const DEFAULT_TIMEOUT_MS: u64 = 120_000;
+pub const DEFAULT_MAX_REDIRECTS: u32 = 5;
+pub const DEFAULT_MAX_BODY_BYTES: u64 = 10 * 1024 * 1024;
+
+#[derive(Debug)]
+pub struct UreqClient {
+    defaults: HttpLimits,
+    /// Timeouts and redirects are agent settings, so there is one agent per combination in use.
+    agents: Mutex<HashMap<(u64, u64, u64, u32), ureq::Agent>>,
+}
+
+impl Default for UreqClient {
+    fn default() -> Self {
+        UreqClient::new(HttpLimits::default())
+    }
+}
+
+impl UreqClient {
+    /// `defaults` apply to every step that doesn't set its own limits.
+    pub fn new(defaults: HttpLimits) -> Self {
+        UreqClient {
+            defaults,
+            agents: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn agent(&self, connect_timeout_ms: u64, read_timeout_ms: u64, timeout_ms: u64, max_redirects: u32) -> ureq::Agent {
+        let mut agents = self.agents.lock().unwrap();
+        agents
+            .entry((connect_timeout_ms, read_timeout_ms, timeout_ms, max_redirects))
+            .or_insert_with(|| {
+                ureq::AgentBuilder::new()
+                    .timeout_connect(Duration::from_millis(connect_timeout_ms))
+                    .timeout_read(Duration::from_millis(read_timeout_ms))
+                    // A server trickling bytes never hits the read timeout, the overall one still ends the request.
+                    .timeout(Duration::from_millis(timeout_ms))
+                    .redirects(max_redirects)
+                    .build()
+            })
+            .clone()
+    }
+}
+
+impl HttpClient for UreqClient {
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Box<dyn Error>> {
+        let limits = request.limits.or(&self.defaults);
+        let max_body_bytes = limits.max_body_bytes.unwrap_or(DEFAULT_MAX_BODY_BYTES);
+        let agent = self.agent(
+            limits.connect_timeout_ms.unwrap_or(DEFAULT_CONNECT_TIMEOUT_MS),
+            limits.read_timeout_ms.unwrap_or(DEFAULT_READ_TIMEOUT_MS),
+            limits.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS),
+            limits.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS),
+        );
+
+        let mut req = agent.request(&request.method, &request.url);
+        for (name, value) in &request.query {
+            req = req.query(name, value);
+        }
+        for (name, value) in &request.headers {
+            req = req.set(name, value);
+        }
+
+        let result = match &request.body {
+            None => req.call(),
+            Some(body) => req.send_string(body),
+        };
+        // 4xx and 5xx are still responses, whether they fail the step is decided by the caller.
+        let response = match result {
+            Ok(response) => response,
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(e.into()),
+        };
+
+        let status = response.status();
+        if !limits.content_types.is_empty() {
+            let content_type = response.content_type();
+            if !limits.content_types.iter().any(|accepted| content_type_matches(accepted, content_type)) {
+                return Err(format!("{} {} returned content type {}, expected one of {:?}", request.method, request.url, content_type, limits.content_types).into());
+            }
+        }
+        if let Some(length) = response.header("content-length").and_then(|length| length.parse::<u64>().ok()) {
+            if length > max_body_bytes {
+                return Err(format!("{} {} returned {} bytes, the limit is {}", request.method, request.url, length, max_body_bytes).into());
+            }
+        }
+
+        let headers = response
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| response.header(&name).map(|value| (name.clone(), value.to_owned())))
+            .collect();
+        // Reading one byte past the limit tells a body of exactly the limit from a longer one.
+        let mut bytes = vec![];
+        response.into_reader().take(max_body_bytes + 1).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > max_body_bytes {
+            return Err(format!("{} {} returned more than {} bytes", request.method, request.url, max_body_bytes).into());
+        }
+        let body = String::from_utf8_lossy(&bytes).into_owned();
+
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+            cache: None,
+        })
+    }
+}
+
+/// `text/*` accepts any text type, parameters such as the charset are ignored.
+fn content_type_matches(accepted: &str, content_type: &str) -> bool {
+    let accepted = accepted.trim().to_lowercase();
+    let content_type = content_type.trim().to_lowercase();
+    match accepted.strip_suffix("/*") {
+        Some(family) => content_type.split('/').next() == Some(family),
+        None => accepted == content_type,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::MultipartField;
+
+    fn file(name: &str, filename: &str) -> HttpBody {
+        HttpBody::Multipart(vec![MultipartField {
+            name: name.to_owned(),
+            value: "content".to_owned(),
+            filename: Some(filename.to_owned()),
+            content_type: None,
+        }])
+    }
+
+    #[test]
+    fn multipart_quotes_are_percent_encoded() {
+        let mut context = ExecutionContext::new();
+        context.insert("upload", json!({"name": "a\"b.txt"}));
+        let (_, body) = render_body(&file("up\"load", "{{context.upload.name}}"), &context).unwrap();
+        assert!(body.contains("Content-Disposition: form-data; name=\"up%22load\"; filename=\"a%22b.txt\"\r\n"));
+    }
+
+    #[test]
+    fn multipart_line_breaks_are_an_error() {
+        let mut context = ExecutionContext::new();
+        context.insert("upload", json!({"name": "a.txt\r\nX-Injected: 1"}));
+        assert!(render_body(&file("upload", "{{context.upload.name}}"), &context).is_err());
+    }
+}
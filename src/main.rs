@@ -1,288 +1,285 @@
-use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process;
 
-use aws_config::meta::region::RegionProviderChain;
-use aws_sdk_dynamodb::{Client, Error as DynamoDBError};
-use aws_sdk_dynamodb::model::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use clap::Parser;
 
+use crate::cache::{CacheStore, CachingClient, DirCache, MemoryCache};
+use crate::cassette::{RecordingClient, ReplayClient};
+use crate::checkpoint::{CheckpointStore, DynamoCheckpointStore, FileCheckpointStore};
+use crate::cli::{Cli, Command, Format, GraphFormat, HttpArgs, Persistence, Source};
+use crate::history::DynamoHistory;
+use crate::http::{HttpClient, UreqClient};
 use crate::models::{
+    ColumnType,
     ConvertDefinition,
-    ConvertOption,
-    ConvertStrType,
+    ConvertSource,
+    ConvertType,
+    CsvParse,
+    HttpAuth,
+    HttpBody,
+    HttpCache,
     HttpDefinition,
-    HttpStrType,
+    HttpExpect,
+    HttpExtract,
+    HttpLimits,
     HttpType,
+    JsonParse,
+    JsonSelector,
+    MultipartField,
     ParseDefinition,
-    ParseStrType,
+    ParseInput,
     ParseType,
+    RateLimit,
+    RegexParse,
     StateMachine,
     Step,
     StepDefinition,
-    StrOrNum,
-    StrStep,
+    TableParse,
+    TextFormat,
+    TextParse,
+    XPathSelector,
+    XmlOutput,
+    XmlParse,
 };
+use crate::ratelimit::{HostLimiter, RateLimitedClient, SystemClock};
 
+mod cache;
+mod cassette;
+mod checkpoint;
+mod cli;
+mod context;
+mod convert;
+mod dynamo;
+mod files;
+mod graph;
+mod history;
+mod html;
+mod http;
 mod models;
+mod parse;
+mod ratelimit;
 mod serializer;
+mod template;
 mod utils;
-
-const DB_TABLE: &str = "ParseStep";
+mod validate;
 
 #[tokio::main]
-async fn main() -> Result<(), DynamoDBError> {
-    utils::print_wrap("configuring DynamoDB", '»');
-
-    let region_provider = RegionProviderChain::default_provider().or_else("us-east-1");
-    let config = aws_config::from_env().region(region_provider).load().await;
-    let client = Client::new(&config);
-
-    utils::print_wrap("requesting steps from DynamoDB", '»');
-
-    let resp = client.scan().table_name(DB_TABLE).send().await?;
-
-    utils::print_wrap("response successful with steps from DynamoDB", '»');
-
-    if let Some(item) = resp.items {
-        let mut vec_steps = vec![];
-        for i in item {
-            vec_steps.push(value_to_item(i));
-        }
-
-        utils::print_wrap("parsing started", '»');
+async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
 
-        let steps_str = serde_json::to_string_pretty(&vec_steps).unwrap();
-        let steps = serializer::run_parse(steps_str.as_str());
-
-        utils::print_wrap("Parsing finished successfully", '»');
-
-        match steps {
-            Ok(steps) => {
-                if !steps.is_empty() {
-                    let first = steps.first().unwrap().to_owned();
-
-                    StateMachine::new(first)
-                        .insert_batch(steps)
-                        .run();
+    match cli.command {
+        Command::Run { source, persistence, http } => {
+            if let Some(state_machine) = build(&source, &persistence, &http, None).await? {
+                if !state_machine.run() {
+                    process::exit(1);
                 }
             }
-            Err(e) => println!("{}", e)
-        };
-    }
-
-    Ok(())
-}
-
-fn value_to_item(item: HashMap<String, AttributeValue>) -> StrStep {
-    let typ = item.keys();
-    let mut typ_str = format!("");
-    let mut uuid = format!("");
-    let mut name = format!("");
-    let mut error_on_fail = format!("");
-    let mut created_at = format!("");
-    let mut next = None;
-    let mut http = None;
-    let mut parse = None;
-    let mut convert = None;
-    for key in typ {
-        let value = item.get(key);
-        match key.as_str() {
-            "type" => {
-                match value {
-                    None => {}
-                    Some(value) => {
-                        match value {
-                            AttributeValue::S(t) => {
-                                typ_str = t.to_owned();
-                            }
-                            _ => {}
-                        }
-                    }
+        }
+        Command::Resume { execution_id, source, persistence, http } => {
+            if let Some(state_machine) = build(&source, &persistence, &http, Some(&execution_id)).await? {
+                if !state_machine.resume(&execution_id)? {
+                    process::exit(1);
                 }
             }
-            "uuid" => {
-                match value {
-                    None => {}
-                    Some(value) => {
-                        match value {
-                            AttributeValue::S(string) => {
-                                uuid = string.to_owned()
-                            }
-                            _ => {}
-                        }
+        }
+        Command::Validate { paths, table, workflow, format } => {
+            let values = load_values(&paths, table.as_deref(), workflow.as_deref()).await?;
+            let report = validate::validate(&values);
+            match format {
+                Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+                Format::Human => {
+                    for diagnostic in &report.diagnostics {
+                        println!("{}", diagnostic);
                     }
+                    println!("{} steps, {} errors, {} warnings", report.steps, report.errors(), report.warnings());
                 }
             }
-            "error_on_fail" => {
-                match value {
-                    None => {}
-                    Some(value) => {
-                        match value {
-                            AttributeValue::S(value) => {
-                                error_on_fail = value.to_owned()
-                            }
-                            _ => {}
-                        }
-                    }
-                }
+            if !report.valid {
+                process::exit(1);
             }
-            "next" => {
-                match value {
-                    None => {}
-                    Some(value) => {
-                        match value {
-                            AttributeValue::S(value) => {
-                                match value.is_empty() {
-                                    true => {}
-                                    false => {
-                                        next = Some(value.to_owned())
-                                    }
-                                }
-                            }
-                            _ => {}
-                        }
+        }
+        Command::Graph { source, format, execution, history_table } => {
+            let events = match &execution {
+                None => vec![],
+                Some(execution_id) => {
+                    let history = DynamoHistory::new(dynamo::client().await, &history_table);
+                    let events = history.events(execution_id).await?;
+                    if events.is_empty() {
+                        return Err(format!("no history for execution {}", execution_id).into());
                     }
+                    events
                 }
-            }
-            "created_at" => {
-                match value {
-                    None => {}
-                    Some(value) => {
-                        match value {
-                            AttributeValue::S(value) => {
-                                created_at = value.to_owned()
-                            }
-                            _ => {}
-                        }
-                    }
+            };
+            // Without an explicit workflow the traced execution tells which one to load.
+            let workflow_id = source.workflow.clone()
+                .or_else(|| events.first().and_then(|event| event.workflow_id.clone()));
+
+            let paths: Vec<PathBuf> = source.dir.iter().chain(source.file.iter()).cloned().collect();
+            let table = match source.is_local() {
+                true => None,
+                false => Some(source.table.as_str()),
+            };
+            // Broken steps are left out so the rest of the workflow can still be drawn.
+            let mut steps = vec![];
+            for value in load_values(&paths, table, workflow_id.as_deref()).await? {
+                match serializer::parse_step(&value) {
+                    Ok(step) => steps.push(step),
+                    Err(e) => eprintln!("skipping step {}: {}", value.get("name").unwrap_or(&value), e),
                 }
             }
-            "name" => {
-                match value {
-                    None => {}
-                    Some(value) => {
-                        match value {
-                            AttributeValue::S(value) => {
-                                name = value.to_owned()
-                            }
-                            _ => {}
-                        }
-                    }
-                }
+            let mut graph = graph::Graph::new(&steps);
+            if execution.is_some() {
+                graph = graph.with_trace(graph::Trace::from_events(&events));
             }
-            "http" => {
-                match value {
-                    None => {}
-                    Some(value) => {
-                        match value {
-                            AttributeValue::M(http_) => {
-                                let typ = extract_s_or_default(http_.get("type"));
-                                let url = extract_s_or_default(http_.get("url"));
-                                let body = extract_s_or_default(http_.get("body"));
-                                http = match typ.to_lowercase().as_str() {
-                                    "get" => {
-                                        Some(HttpStrType {
-                                            typ,
-                                            url,
-                                            body: None,
-                                        })
-                                    }
-                                    "post" => {
-                                        Some(HttpStrType {
-                                            typ,
-                                            url,
-                                            body: Some(body),
-                                        })
-                                    }
-                                    _ => None
-                                };
-                            }
-                            _ => {}
-                        }
-                    }
-                }
+            match format {
+                GraphFormat::Dot => print!("{}", graph.to_dot()),
+                GraphFormat::Mermaid => print!("{}", graph.to_mermaid()),
             }
-            "parse" => {
-                match value {
-                    None => {}
-                    Some(value) => {
-                        match value {
-                            AttributeValue::M(parse_) => {
-                                let typ = extract_s_or_default(parse_.get("type"));
-                                let content = extract_s_or_default(parse_.get("content"));
-                                parse = Some(ParseStrType {
-                                    typ,
-                                    content,
-                                })
-                            }
-                            _ => {}
-                        }
-                    }
-                }
+        }
+        Command::Executions { workflow_id, history_table } => {
+            let history = DynamoHistory::new(dynamo::client().await, &history_table);
+            for execution in history.list_executions(&workflow_id).await? {
+                println!("{} {}", execution.timestamp, execution.execution_id);
             }
-            "convert" => {
-                match value {
-                    None => {}
-                    Some(value) => {
-                        match value {
-                            AttributeValue::M(convert_) => {
-                                let typ = extract_s_or_default(convert_.get("type"));
-                                let from = extract_s_or_default(convert_.get("from"));
-                                let to = extract_s_or_default(convert_.get("to"));
-                                let source = convert_.get("source");
-                                match source {
-                                    None => {}
-                                    Some(source) => {
-                                        convert = match source {
-                                            AttributeValue::N(source) => {
-                                                Some(ConvertStrType {
-                                                    typ,
-                                                    from,
-                                                    to,
-                                                    source: StrOrNum::Num(source.to_owned().parse::<i32>().unwrap()),
-                                                })
-                                            }
-                                            AttributeValue::Null(_) => None,
-                                            AttributeValue::S(source) => {
-                                                Some(ConvertStrType {
-                                                    typ,
-                                                    from,
-                                                    to,
-                                                    source: StrOrNum::Str(source.to_owned()),
-                                                })
-                                            }
-                                            _ => None
-                                        };
-                                    }
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                }
+        }
+        Command::Events { execution_id, history_table } => {
+            let history = DynamoHistory::new(dynamo::client().await, &history_table);
+            let events = history.events(&execution_id).await?;
+            println!("{}", serde_json::to_string_pretty(&events)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Loads the steps and wires up history and checkpoints. DynamoDB is only contacted when
+/// the steps or the persistence live there, so local files can be run offline.
+async fn build(source: &Source, persistence: &Persistence, http: &HttpArgs, resume_id: Option<&str>) -> Result<Option<StateMachine>, Box<dyn Error>> {
+    let history_table = persistence.history_table(source);
+    let checkpoint_table = persistence.checkpoint_table(source);
+    let client = match !source.is_local() || history_table.is_some() || checkpoint_table.is_some() {
+        true => {
+            utils::print_wrap("configuring DynamoDB", '»');
+            Some(dynamo::client().await)
+        }
+        false => None,
+    };
+
+    let checkpoints: Option<Box<dyn CheckpointStore>> = match (&persistence.checkpoint_dir, &checkpoint_table, &client) {
+        (Some(dir), _, _) => Some(Box::new(FileCheckpointStore::new(dir))),
+        (None, Some(table), Some(client)) => Some(Box::new(DynamoCheckpointStore::new(client.clone(), table))),
+        _ => None,
+    };
+
+    // A resumed execution reloads the workflow it was started with.
+    let workflow_id = match (resume_id, &checkpoints) {
+        (Some(execution_id), Some(checkpoints)) => match checkpoints.load(execution_id)? {
+            None => return Err(format!("no checkpoint for execution {}", execution_id).into()),
+            Some(checkpoint) => checkpoint.workflow_id.or_else(|| source.workflow.clone()),
+        },
+        (Some(_), None) => return Err("resume needs a checkpoint store".into()),
+        (None, _) => source.workflow.clone(),
+    };
+
+    let steps = load_steps(source, client.as_ref(), workflow_id.as_deref()).await?;
+
+    if steps.is_empty() {
+        return Ok(None);
+    }
+
+    // The first step names the start, which resolves to the last definition of that name like every `next` does.
+    let start_name = &steps.first().unwrap().name;
+    let first = steps.iter().rev().find(|step| &step.name == start_name).unwrap().to_owned();
+    let http_client: Box<dyn HttpClient> = match &http.replay {
+        // The cassette holds what the steps received, cache hits included, so replays skip the cache.
+        Some(cassette) => Box::new(ReplayClient::load(cassette)?),
+        None => {
+            let cache: Box<dyn CacheStore> = match &http.http_cache_dir {
+                None => Box::new(MemoryCache::default()),
+                Some(dir) => Box::new(DirCache::new(dir)),
+            };
+            // Cache hits never reach the rate limiter, but they are recorded like any other response.
+            let network = RateLimitedClient::new(
+                Box::new(UreqClient::new(http.limits())),
+                HostLimiter::new(http.rate_limit(), Box::new(SystemClock)),
+            );
+            let cached = Box::new(CachingClient::new(Box::new(network), cache));
+            match &http.record {
+                Some(cassette) => Box::new(RecordingClient::new(cached, cassette)),
+                None => cached,
             }
-            _ => {}
         }
+    };
+    let mut state_machine = StateMachine::new(first)
+        .insert_batch(steps)
+        .with_http(http_client);
+    if let (Some(table), Some(client)) = (history_table, &client) {
+        state_machine = state_machine.with_history(Box::new(DynamoHistory::new(client.clone(), &table)));
     }
-    StrStep {
-        typ: typ_str,
-        uuid,
-        name,
-        error_on_fail,
-        created_at,
-        next,
-        http,
-        parse,
-        convert,
+    if let Some(checkpoints) = checkpoints {
+        state_machine = state_machine.with_checkpoints(checkpoints);
     }
+
+    Ok(Some(state_machine))
+}
+
+async fn load_steps(source: &Source, client: Option<&Client>, workflow_id: Option<&str>) -> Result<Vec<Step>, Box<dyn Error>> {
+    let steps = match (&source.dir, client) {
+        (Some(dir), _) => {
+            let mut steps = files::load_dir(dir)?;
+            steps.extend(files::load_files(&source.file)?);
+            steps
+        }
+        (None, _) if source.is_local() => files::load_files(&source.file)?,
+        (None, Some(client)) => {
+            utils::print_wrap("requesting steps from DynamoDB", '»');
+
+            let vec_steps = match workflow_id {
+                None => dynamo::scan_steps(client, &source.table).await?,
+                Some(workflow_id) => dynamo::query_workflow(client, &source.table, workflow_id).await?,
+            };
+
+            utils::print_wrap("response successful with steps from DynamoDB", '»');
+
+            utils::print_wrap("parsing started", '»');
+
+            let steps_str = serde_json::to_string_pretty(&vec_steps)?;
+            let steps = serializer::run_parse(steps_str.as_str())?;
+
+            utils::print_wrap("Parsing finished successfully", '»');
+
+            steps
+        }
+        (None, None) => unreachable!("DynamoDB client is created for non local sources"),
+    };
+    Ok(steps)
 }
 
-fn extract_s_or_default(s: Option<&AttributeValue>) -> String {
-    match s {
-        None => format!(""),
-        Some(s) => {
-            match s {
-                AttributeValue::S(string) => string.to_owned(),
-                _ => format!("")
+/// Raw step objects of the files, directories and DynamoDB table, for commands that look at
+/// every step on its own instead of failing on the first broken one.
+async fn load_values(paths: &[PathBuf], table: Option<&str>, workflow_id: Option<&str>) -> Result<Vec<serde_json::Value>, Box<dyn Error>> {
+    let mut values = vec![];
+    for path in paths {
+        match path.is_dir() {
+            true => {
+                for file in files::step_files(path)? {
+                    values.extend(files::load_values(&file)?);
+                }
             }
+            false => values.extend(files::load_values(path)?),
+        }
+    }
+    if let Some(table) = table {
+        let client = dynamo::client().await;
+        let vec_steps = match workflow_id {
+            None => dynamo::scan_steps(&client, table).await?,
+            Some(workflow_id) => dynamo::query_workflow(&client, table, workflow_id).await?,
+        };
+        for step in vec_steps {
+            values.push(serde_json::to_value(step)?);
         }
     }
+    Ok(values)
 }
@@ -1,13 +1,27 @@
 use std::collections::HashMap;
 use std::error::Error;
-use crate::utils;
-use serde::{Serialize, Serializer};
+use std::rc::Rc;
+use std::time::Instant;
+use crate::cache::CacheStatus;
+use crate::checkpoint::{Checkpoint, CheckpointStatus, CheckpointStore};
+use crate::context::ExecutionContext;
+use crate::history::{EventType, Execution, ExecutionHistory};
+use crate::http::{HttpClient, HttpRequest, UreqClient};
+use crate::{convert, parse, utils};
+use chrono::{Locale, Utc};
+use chrono_tz::Tz;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use serde_json_path::JsonPath;
+use sxd_xpath::XPath;
+use uuid::Uuid;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum StepDefinition {
     Convert(ConvertDefinition),
     Parse(ParseDefinition),
-    Http(HttpDefinition),
+    Http(Box<HttpDefinition>),
 }
 
 #[derive(Debug, Serialize)]
@@ -15,36 +29,38 @@ pub struct HttpStrType {
     #[serde(rename = "type")]
     pub typ: String,
     pub url: String,
-    pub body: Option<String>
+    pub body: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub form: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multipart: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headers: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expect: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extract: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limits: Option<Value>,
+    #[serde(rename = "rateLimit", skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache: Option<Value>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ParseStrType {
     #[serde(rename = "type")]
     pub typ: String,
-    pub content: String
-}
-
-#[derive(Debug)]
-pub enum StrOrNum {
-    Str(String),
-    Num(i32)
-}
-
-impl Serialize for StrOrNum {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-        where
-            S: Serializer,
-    {
-        match self {
-            StrOrNum::Str(str) => {
-                serializer.serialize_str(str)
-            }
-            StrOrNum::Num(num) => {
-                serializer.serialize_i32(num.clone() as i32)
-            }
-        }
-    }
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// Options of the parse type, e.g. `source` and `extract`.
+    #[serde(flatten)]
+    pub options: Map<String, Value>,
 }
 
 #[derive(Debug, Serialize)]
@@ -53,7 +69,11 @@ pub struct ConvertStrType {
     pub typ: String,
     pub from: String,
     pub to: String,
-    pub source: StrOrNum
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Value>,
+    /// Options of the conversion, e.g. `toFormat` and `timezone`.
+    #[serde(flatten)]
+    pub options: Map<String, Value>,
 }
 
 #[derive(Debug, Serialize)]
@@ -61,12 +81,16 @@ pub struct StrStep {
     #[serde(rename = "type")]
     pub typ: String,
     pub uuid: String,
+    #[serde(rename = "workflowId")]
+    pub workflow_id: Option<String>,
     pub name: String,
     #[serde(rename = "errorOnFail")]
     pub error_on_fail: String,
     #[serde(rename = "createdAt")]
     pub created_at: String,
     pub next: Option<String>,
+    /// Kept as the stored number, so the step parser reports values that don't fit.
+    pub retries: Option<Value>,
     pub http: Option<HttpStrType>,
     pub parse: Option<ParseStrType>,
     pub convert: Option<ConvertStrType>
@@ -75,16 +99,170 @@ pub struct StrStep {
 #[derive(Debug, PartialEq, Clone)]
 pub struct Step {
     pub uuid: String,
+    pub workflow_id: Option<String>,
     pub name: String,
     pub error_on_fail: String,
     pub created_at: String,
     pub definition: StepDefinition,
     pub next: Option<String>,
+    pub retries: u32,
+}
+
+impl Step {
+    /// Key of the step's output in the execution context: the step name, or the `target` of a convert step.
+    pub fn output_key(&self) -> &str {
+        match &self.definition {
+            StepDefinition::Convert(ConvertDefinition { target: Some(target), .. }) => target,
+            _ => &self.name,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum ParseType {
     Document(String),
+    Json(JsonParse),
+    Xml(XmlParse),
+    Csv(CsvParse),
+    Regex(RegexParse),
+    Table(TableParse),
+    Text(TextParse),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct XmlParse {
+    pub input: ParseInput,
+    /// Prefixes usable in the expressions, they don't have to match the prefixes of the document.
+    pub namespaces: Vec<(String, String)>,
+    pub extract: Vec<(String, XPathSelector)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct XPathSelector {
+    /// XPath 1.0.
+    pub xpath: String,
+    /// `xpath` compiled when the step is parsed, every run reuses it. `XPath` can't be cloned, so copies of the step share it.
+    pub compiled: Rc<XPath>,
+    pub output: XmlOutput,
+}
+
+/// `XPath` can't be compared, the expression it was compiled from can.
+impl PartialEq for XPathSelector {
+    fn eq(&self, other: &Self) -> bool {
+        self.xpath == other.xpath && self.output == other.output
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum XmlOutput {
+    /// Booleans, numbers and strings as they are, node lists as an array of their text.
+    Auto,
+    String,
+    Number,
+    /// Array of the text of each node.
+    Nodes,
+    /// Array of the nodes as XML sub-documents.
+    Xml,
+}
+
+/// Output is an array with an object per row.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CsvParse {
+    pub input: ParseInput,
+    pub delimiter: u8,
+    /// `None` reads quote characters as plain text.
+    pub quote: Option<u8>,
+    /// When the first row is not a header, `columns` names the columns, otherwise they are named by their index.
+    pub has_header: bool,
+    pub columns: Vec<String>,
+    /// Columns without a hint stay strings. Empty cells of typed columns become `null`.
+    pub types: Vec<(String, ColumnType)>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColumnType {
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+impl ColumnType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColumnType::String => "string",
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Bool => "bool",
+        }
+    }
+}
+
+/// Named capture groups become the keys of the output, a pattern without them outputs the matched text.
+#[derive(Debug, Clone)]
+pub struct RegexParse {
+    pub input: ParseInput,
+    pub pattern: String,
+    /// Any of `i` (case-insensitive), `m` (multi-line), `s` (`.` matches newlines) and `x` (verbose).
+    pub flags: String,
+    /// `pattern` compiled with `flags` when the step is parsed, every run reuses it.
+    pub regex: Regex,
+    /// Outputs an array of every match instead of the first one.
+    pub all: bool,
+}
+
+/// `Regex` can't be compared, the pattern and flags it was compiled from can.
+impl PartialEq for RegexParse {
+    fn eq(&self, other: &Self) -> bool {
+        self.input == other.input && self.pattern == other.pattern && self.flags == other.flags && self.all == other.all
+    }
+}
+
+/// Output is an array with an object per body row, keyed by the header cells.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TableParse {
+    pub input: ParseInput,
+    /// CSS selector of the table, the first match is used. Checked when the step is parsed.
+    pub selector: String,
+}
+
+/// Output is the readable text of an HTML document.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextParse {
+    pub input: ParseInput,
+    /// CSS selector of the parts to convert, every match is. Checked when the step is parsed.
+    pub selector: Option<String>,
+    pub format: TextFormat,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TextFormat {
+    Text,
+    Markdown,
+}
+
+/// What a parse step reads.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseInput {
+    /// Literal `content` of the definition.
+    Content(String),
+    /// `source` path into the execution context, e.g. `fetch.body`.
+    Source(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct JsonParse {
+    pub input: ParseInput,
+    /// Without rules the output is the whole document, otherwise an object of the named values.
+    pub extract: Vec<(String, JsonSelector)>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum JsonSelector {
+    /// JSON Pointer, e.g. `/items/0/title`. The value must exist.
+    Pointer(String),
+    /// JSONPath, e.g. `$.items[*].title`, compiled when the step is parsed. Always yields an array of the matches.
+    Path(JsonPath),
 }
 
 impl PartialEq<ParseType> for ParseDefinition {
@@ -104,44 +282,293 @@ pub struct ParseDefinition {
     pub parse_type: ParseType,
 }
 
-#[derive(Debug, Clone)]
-pub enum ConvertOption {
-    FromIntToString(i32),
-    FromStringToInt(String),
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ConvertType {
+    String,
+    /// 64-bit signed integer.
+    Int,
+    Float,
+    Bool,
+    /// Exact decimal number, output as a string so no digits are lost.
+    Decimal,
+    /// Date and time, read from a string or a unix timestamp and output as a string.
+    DateTime,
+    /// Any JSON value. A string source is parsed, a string output is serialized.
+    Json,
+    /// Bytes as base64 text.
+    Base64,
+    /// Bytes as hexadecimal text.
+    Hex,
 }
 
-impl PartialEq for ConvertOption {
-    fn eq(&self, other: &Self) -> bool {
-        self == other
+impl ConvertType {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(ConvertType::String),
+            "int" => Some(ConvertType::Int),
+            "float" => Some(ConvertType::Float),
+            "bool" => Some(ConvertType::Bool),
+            "decimal" => Some(ConvertType::Decimal),
+            "datetime" => Some(ConvertType::DateTime),
+            "json" => Some(ConvertType::Json),
+            "base64" => Some(ConvertType::Base64),
+            "hex" => Some(ConvertType::Hex),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConvertType::String => "string",
+            ConvertType::Int => "int",
+            ConvertType::Float => "float",
+            ConvertType::Bool => "bool",
+            ConvertType::Decimal => "decimal",
+            ConvertType::DateTime => "datetime",
+            ConvertType::Json => "json",
+            ConvertType::Base64 => "base64",
+            ConvertType::Hex => "hex",
+        }
+    }
+
+    /// The conversion matrix. Strings and JSON convert to and from everything, numbers and booleans among
+    /// each other, bytes among their encodings and dates to unix timestamps. Only dates convert to themselves,
+    /// to change their format or time zone.
+    pub fn converts_to(&self, to: ConvertType) -> bool {
+        use ConvertType::*;
+        match (*self, to) {
+            (DateTime, DateTime) => true,
+            (from, to) if from == to => false,
+            (String | Json, _) | (_, String | Json) => true,
+            (Int | Float | Bool | Decimal, Int | Float | Bool | Decimal) => true,
+            (Base64 | Hex, Base64 | Hex) => true,
+            (DateTime, Int) | (Int, DateTime) => true,
+            _ => false,
+        }
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ConvertDefinition {
-    pub source: ConvertOption,
+    pub from: ConvertType,
+    pub to: ConvertType,
+    pub source: ConvertSource,
+    /// `chrono` format of date sources, RFC 3339 and a few common layouts are tried when unset.
+    pub from_format: Option<String>,
+    /// `chrono` format of date outputs, RFC 3339 when unset.
+    pub to_format: Option<String>,
+    /// Zone of date outputs and of date sources without an offset. When unset dates keep the offset they were
+    /// read with, and sources without one are UTC.
+    pub timezone: Option<Tz>,
+    /// Context key the output is stored under instead of the step name.
+    pub target: Option<String>,
+    /// Language of the month and day names in date sources and `toFormat` outputs.
+    pub locale: Option<Locale>,
+    /// Set when numbers are read the way `locale` or the separator options write them.
+    pub number_format: Option<NumberFormat>,
 }
 
-#[derive(Debug, Clone)]
+/// How a locale writes numbers. Text around a number may hold a sign, a currency and a percent sign.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NumberFormat {
+    pub decimal_separator: char,
+    /// `None` when numbers are not grouped. Whitespace always separates groups.
+    pub thousands_separator: Option<char>,
+    /// Currency symbols and codes of the locale, on top of the common symbols and three-letter codes.
+    pub currencies: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConvertSource {
+    /// Value of the definition, checked against `from` when the step is parsed.
+    Literal(Value),
+    /// `{{context.step.path}}`, the value at the path as it is.
+    Context(String),
+    /// String with placeholders, rendered like http templates.
+    Template(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum HttpType {
     Get(String),
-    Post(String, String),
+    Post(String, HttpBody),
+    Put(String, Option<HttpBody>),
+    Patch(String, Option<HttpBody>),
+    Delete(String, Option<HttpBody>),
+    Head(String),
 }
 
-impl PartialEq for HttpType {
-    fn eq(&self, other: &Self) -> bool {
-        self == other
+/// Request body. String values, also those nested in `Json`, may contain placeholders.
+#[derive(Debug, PartialEq, Clone)]
+pub enum HttpBody {
+    /// Sent as it is.
+    Raw(String),
+    /// Sent as `application/json`.
+    Json(Value),
+    /// Sent as `application/x-www-form-urlencoded`.
+    Form(Vec<(String, String)>),
+    /// Sent as `multipart/form-data`.
+    Multipart(Vec<MultipartField>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MultipartField {
+    pub name: String,
+    pub value: String,
+    /// Set for file parts.
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+}
+
+impl HttpType {
+    pub fn method(&self) -> &'static str {
+        match self {
+            HttpType::Get(_) => "GET",
+            HttpType::Post(_, _) => "POST",
+            HttpType::Put(_, _) => "PUT",
+            HttpType::Patch(_, _) => "PATCH",
+            HttpType::Delete(_, _) => "DELETE",
+            HttpType::Head(_) => "HEAD",
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        match self {
+            HttpType::Get(url) | HttpType::Head(url) => url,
+            HttpType::Post(url, _) => url,
+            HttpType::Put(url, _) | HttpType::Patch(url, _) | HttpType::Delete(url, _) => url,
+        }
+    }
+
+    pub fn body(&self) -> Option<&HttpBody> {
+        match self {
+            HttpType::Get(_) | HttpType::Head(_) => None,
+            HttpType::Post(_, body) => Some(body),
+            HttpType::Put(_, body) | HttpType::Patch(_, body) | HttpType::Delete(_, body) => body.as_ref(),
+        }
     }
 }
 
+/// Credentials added to the request. Values may contain `{{secrets.NAME}}` and `{{context.step}}` placeholders.
+#[derive(Debug, PartialEq, Clone)]
+pub enum HttpAuth {
+    Bearer(String),
+    Basic { username: String, password: String },
+    ApiKey { header: String, value: String },
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct HttpDefinition {
     pub http_type: HttpType,
+    /// Header and query values may contain placeholders, like the auth values.
+    pub headers: Vec<(String, String)>,
+    pub query: Vec<(String, String)>,
+    pub auth: Option<HttpAuth>,
+    /// Without expectations every status below 400 succeeds.
+    pub expect: Option<HttpExpect>,
+    /// When set, the output of the step is an object of these named values instead of the whole response.
+    pub extract: Vec<(String, HttpExtract)>,
+    pub limits: HttpLimits,
+    /// Throttling of the request's host, unset values fall back to the engine-wide rate limit.
+    pub rate_limit: RateLimit,
+    /// Only GET steps can be cached.
+    pub cache: Option<HttpCache>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct HttpCache {
+    /// Freshness of responses without a `Cache-Control: max-age`. Without it they are always revalidated.
+    pub ttl_seconds: Option<u64>,
+}
+
+/// Requests a host accepts from the engine. Every request to the host counts, whichever step or execution sends it.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RateLimit {
+    pub requests_per_second: Option<f64>,
+    pub max_concurrent: Option<u32>,
+}
+
+impl RateLimit {
+    pub fn or(&self, defaults: &RateLimit) -> RateLimit {
+        RateLimit {
+            requests_per_second: self.requests_per_second.or(defaults.requests_per_second),
+            max_concurrent: self.max_concurrent.or(defaults.max_concurrent),
+        }
+    }
+}
+
+/// Bounds of a request. Unset values fall back to the engine-wide limits of the http client.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct HttpLimits {
+    pub connect_timeout_ms: Option<u64>,
+    /// Longest wait for the next bytes of the response.
+    pub read_timeout_ms: Option<u64>,
+    /// Deadline of the whole request, from connecting to the last byte of the body.
+    pub timeout_ms: Option<u64>,
+    /// `0` doesn't follow redirects.
+    pub max_redirects: Option<u32>,
+    pub max_body_bytes: Option<u64>,
+    /// Accepted response content types, e.g. `text/html` or `text/*`. Empty accepts any.
+    pub content_types: Vec<String>,
 }
 
-#[derive(Debug, PartialEq)]
+impl HttpLimits {
+    /// These limits, with the unset ones taken from `defaults`.
+    pub fn or(&self, defaults: &HttpLimits) -> HttpLimits {
+        HttpLimits {
+            connect_timeout_ms: self.connect_timeout_ms.or(defaults.connect_timeout_ms),
+            read_timeout_ms: self.read_timeout_ms.or(defaults.read_timeout_ms),
+            timeout_ms: self.timeout_ms.or(defaults.timeout_ms),
+            max_redirects: self.max_redirects.or(defaults.max_redirects),
+            max_body_bytes: self.max_body_bytes.or(defaults.max_body_bytes),
+            content_types: match self.content_types.is_empty() {
+                true => defaults.content_types.clone(),
+                false => self.content_types.clone(),
+            },
+        }
+    }
+}
+
+/// Checks a response has to pass for the step to succeed.
+#[derive(Debug, Clone)]
+pub struct HttpExpect {
+    /// Allowed status codes, replaces the default of any status below 400.
+    pub status: Vec<u16>,
+    /// Headers that must be present and, when a value is given, contain it.
+    pub headers: Vec<(String, Option<String>)>,
+    pub body_contains: Option<String>,
+    /// Compiled when the step is parsed, every response reuses it.
+    pub body_matches: Option<Regex>,
+    /// JSON pointers into the body and the values they must equal.
+    pub json: Vec<(String, Value)>,
+}
+
+/// `Regex` can't be compared, the pattern it was compiled from can.
+impl PartialEq for HttpExpect {
+    fn eq(&self, other: &Self) -> bool {
+        self.status == other.status
+            && self.headers == other.headers
+            && self.body_contains == other.body_contains
+            && self.body_matches.as_ref().map(Regex::as_str) == other.body_matches.as_ref().map(Regex::as_str)
+            && self.json == other.json
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum HttpExtract {
+    /// JSON pointer into the body, e.g. `/data/0/id`.
+    Pointer(String),
+    /// Response header, matched case-insensitively.
+    Header(String),
+}
+
+#[derive(Debug)]
 pub struct StateMachine {
     pub start: Step,
     pub steps: HashMap<String, Step>,
+    pub history: Option<Box<dyn ExecutionHistory>>,
+    pub checkpoints: Option<Box<dyn CheckpointStore>>,
+    pub http: Box<dyn HttpClient>,
 }
 
 impl StateMachine {
@@ -149,39 +576,191 @@ impl StateMachine {
         StateMachine {
             start: step,
             steps: HashMap::new(),
+            history: None,
+            checkpoints: None,
+            http: Box::new(UreqClient::default()),
         }
     }
 
-    pub fn run(&self) {
+    pub fn with_history(mut self, history: Box<dyn ExecutionHistory>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    pub fn with_checkpoints(mut self, checkpoints: Box<dyn CheckpointStore>) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    pub fn with_http(mut self, http: Box<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Runs the machine from the start step and returns whether the execution succeeded.
+    pub fn run(&self) -> bool {
+        let execution_id = Uuid::new_v4().to_string();
+        let mut checkpoint = Checkpoint::new(&execution_id, self.start.workflow_id.clone(), &ExecutionContext::new());
+        self.execute(Some(&self.start), &mut checkpoint, 0, EventType::ExecutionStarted)
+    }
+
+    /// Continues an interrupted execution from the step after the last completed one,
+    /// so the steps that already succeeded are not repeated. Returns whether the execution succeeded.
+    pub fn resume(&self, execution_id: &str) -> Result<bool, Box<dyn Error>> {
+        let store = self.checkpoints.as_deref().ok_or("no checkpoint store configured")?;
+        let mut checkpoint = store
+            .load(execution_id)?
+            .ok_or_else(|| format!("no checkpoint for execution {}", execution_id))?;
+
+        if checkpoint.status == CheckpointStatus::Succeeded {
+            println!("> Execution {} already succeeded", execution_id);
+            return Ok(true);
+        }
+
+        let step = match checkpoint.next_step.clone() {
+            None => None,
+            Some(name) => match self.steps.get(&name) {
+                None => {
+                    return Err(format!("step {} of execution {} does not exist", name, execution_id).into());
+                }
+                Some(step) => Some(step),
+            },
+        };
+
+        // The events of the interrupted run stay in the history, the resumed run appends to them.
+        let sequence = match &self.history {
+            None => 0,
+            Some(history) => history.next_sequence(execution_id)?,
+        };
+        Ok(self.execute(step, &mut checkpoint, sequence, EventType::ExecutionResumed))
+    }
+
+    fn execute(&self, step: Option<&Step>, checkpoint: &mut Checkpoint, sequence: u32, event_type: EventType) -> bool {
+        let execution = Execution::new(
+            checkpoint.execution_id.clone(),
+            checkpoint.workflow_id.clone(),
+            sequence,
+            self.history.as_deref(),
+        );
         utils::print_wrap("state machine started", '»');
-        self.run_next(Some(&self.start));
+        println!("> Execution {}", execution.id);
+
+        let started = Instant::now();
+        execution.execution_event(event_type, None);
+        checkpoint.status = CheckpointStatus::Running;
+        checkpoint.next_step = step.map(|step| step.name.clone());
+        self.save_checkpoint(checkpoint);
+
+        let succeeded = self.run_next(step, &execution, checkpoint);
+        let (event_type, status) = match succeeded {
+            true => (EventType::ExecutionSucceeded, CheckpointStatus::Succeeded),
+            false => (EventType::ExecutionFailed, CheckpointStatus::Failed),
+        };
+        execution.execution_event(event_type, Some(started.elapsed()));
+        checkpoint.status = status;
+        self.save_checkpoint(checkpoint);
+
         utils::print_wrap("end", '»');
+        succeeded
     }
 
-    pub fn run_next(&self, step: Option<&Step>) {
+    pub fn run_next(&self, step: Option<&Step>, execution: &Execution, checkpoint: &mut Checkpoint) -> bool {
         match step {
-            None => {}
-            Some(some) => match self.run_single(some) {
-                Ok(_result) => {
+            None => true,
+            Some(some) => match self.run_with_retries(some, execution, &checkpoint.context) {
+                Ok(result) => {
                     let next = self.get_next(some);
-                    self.run_next(next);
+                    checkpoint.context.insert(some.output_key(), result);
+                    checkpoint.last_completed_step = Some(some.name.clone());
+                    checkpoint.next_step = next.map(|next| next.name.clone());
+                    self.save_checkpoint(checkpoint);
+                    self.run_next(next, execution, checkpoint)
                 }
                 Err(_) => {
                     utils::print_wrap("state machine stopped :(", '»');
                     println!("> [{}] {}", some.uuid, some.error_on_fail);
+                    false
                 }
             },
         }
     }
 
-    pub fn run_single(&self, step: &Step) -> Result<(), Box<dyn Error>> {
+    fn save_checkpoint(&self, checkpoint: &mut Checkpoint) {
+        if let Some(store) = &self.checkpoints {
+            checkpoint.updated_at = Utc::now().to_rfc3339();
+            if let Err(e) = store.save(checkpoint) {
+                println!("> [{}] failed to save checkpoint: {}", checkpoint.execution_id, e);
+            }
+        }
+    }
+
+    fn run_with_retries(&self, step: &Step, execution: &Execution, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+        let mut attempt = 1;
+        loop {
+            execution.step_event(EventType::StepStarted, step, attempt, None, None, None);
+            let started = Instant::now();
+            match self.run_single(step, context, execution, attempt) {
+                Ok(output) => {
+                    execution.step_event(EventType::StepSucceeded, step, attempt, Some(started.elapsed()), Some(&output), None);
+                    return Ok(output);
+                }
+                Err(e) if attempt <= step.retries => {
+                    println!("> Step {} failed, retrying ({}/{}) - {}", step.name, attempt, step.retries, e);
+                    execution.step_event(EventType::StepRetried, step, attempt, Some(started.elapsed()), None, Some(e.as_ref()));
+                    attempt += 1;
+                }
+                Err(e) => {
+                    println!("> Step {} failed - {}", step.name, e);
+                    execution.step_event(EventType::StepFailed, step, attempt, Some(started.elapsed()), None, Some(e.as_ref()));
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// `execution` and `attempt` only label the events a step records while it runs, such as cache hits.
+    pub fn run_single(&self, step: &Step, context: &ExecutionContext, execution: &Execution, attempt: u32) -> Result<Value, Box<dyn Error>> {
         println!("[Running started] for {}", step.name);
 
-        // TODO: some compute here based on the step
+        let output = match &step.definition {
+            StepDefinition::Convert(convert) => convert::run(convert, context)?,
+            StepDefinition::Parse(parse) => parse::run(&parse.parse_type, context)?,
+            StepDefinition::Http(http) => {
+                let request = HttpRequest::build(http, context)?;
+                let response = self.http.send(&request)?;
+                match response.cache {
+                    None => println!("> {} {} - {}", request.method, request.url, response.status),
+                    Some(cache) => {
+                        println!("> {} {} - {} (cache {})", request.method, request.url, response.status, cache);
+                        let event_type = match cache {
+                            CacheStatus::Hit | CacheStatus::Revalidated => EventType::HttpCacheHit,
+                            CacheStatus::Miss => EventType::HttpCacheMiss,
+                        };
+                        let detail = json!({"url": request.url, "cache": cache.as_str()});
+                        execution.step_event(event_type, step, attempt, None, Some(&detail), None);
+                    }
+                }
+                match &http.expect {
+                    None if response.status >= 400 => {
+                        return Err(format!("{} {} returned {}", request.method, request.url, response.status).into());
+                    }
+                    None => {}
+                    Some(expect) => {
+                        if let Err(e) = response.check(expect) {
+                            return Err(format!("{} {} - {}", request.method, request.url, e).into());
+                        }
+                    }
+                }
+                match http.extract.is_empty() {
+                    true => response.to_value(),
+                    false => response.extract(&http.extract)?,
+                }
+            }
+        };
 
         println!("> Step {} Succeeded - {}", step.name, step.uuid);
 
-        Ok(())
+        Ok(output)
     }
 
     pub fn insert_batch(mut self, steps: Vec<Step>) -> Self {
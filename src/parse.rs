@@ -0,0 +1,634 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use regex::{Captures, Regex, RegexBuilder};
+use scraper::{ElementRef, Html, Selector};
+use serde_json::{Map, Value};
+use sxd_document::parser;
+use sxd_xpath::nodeset::Node;
+use sxd_xpath::{Context as XPathContext, Value as XPathValue};
+
+use crate::context::ExecutionContext;
+use crate::html::{self, TableRow};
+use crate::models::{ColumnType, CsvParse, JsonParse, JsonSelector, ParseInput, ParseType, RegexParse, TableParse, TextParse, XmlOutput, XmlParse};
+use crate::template;
+
+/// Output of a parse step.
+pub fn run(parse_type: &ParseType, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+    match parse_type {
+        ParseType::Document(content) => Ok(Value::String(content.to_owned())),
+        ParseType::Json(json) => parse_json(json, context),
+        ParseType::Xml(xml) => parse_xml(xml, context),
+        ParseType::Csv(csv) => parse_csv(csv, context),
+        ParseType::Regex(regex) => parse_regex(regex, context),
+        ParseType::Table(table) => parse_table(table, context),
+        ParseType::Text(text) => parse_text(text, context),
+    }
+}
+
+/// What the step reads: the literal content as a string, or the value at the source path.
+fn input(parse_input: &ParseInput, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+    match parse_input {
+        ParseInput::Content(content) => Ok(Value::String(content.to_owned())),
+        ParseInput::Source(source) => match template::lookup(context, source) {
+            None => Err(format!("`{}` is not in the execution context", source).into()),
+            Some(value) => Ok(value.clone()),
+        },
+    }
+}
+
+/// The input as text. Sources holding something else than a string are read as their JSON.
+fn text(parse_input: &ParseInput, context: &ExecutionContext) -> Result<String, Box<dyn Error>> {
+    match input(parse_input, context)? {
+        Value::String(text) => Ok(text),
+        value => Ok(value.to_string()),
+    }
+}
+
+fn parse_json(json: &JsonParse, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+    // A source can already be a value, e.g. the output of an http step with extraction rules.
+    let document = match input(&json.input, context)? {
+        Value::String(text) => serde_json::from_str(&text)?,
+        value => value,
+    };
+    if json.extract.is_empty() {
+        return Ok(document);
+    }
+
+    let mut output = Map::new();
+    for (name, selector) in &json.extract {
+        let value = match selector {
+            JsonSelector::Pointer(pointer) => match document.pointer(pointer) {
+                None => return Err(format!("cannot extract `{}`, `{}` is not in the document", name, pointer).into()),
+                Some(value) => value.clone(),
+            },
+            JsonSelector::Path(path) => Value::Array(path.query(&document).all().into_iter().cloned().collect()),
+        };
+        output.insert(name.to_owned(), value);
+    }
+    Ok(Value::Object(output))
+}
+
+fn parse_xml(xml: &XmlParse, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+    let package = parser::parse(&text(&xml.input, context)?).map_err(|e| format!("invalid XML: {:?}", e))?;
+    let document = package.as_document();
+    let mut xpath_context = XPathContext::new();
+    for (prefix, uri) in &xml.namespaces {
+        xpath_context.set_namespace(prefix, uri);
+    }
+
+    let mut output = Map::new();
+    for (name, selector) in &xml.extract {
+        let value = selector
+            .compiled
+            .evaluate(&xpath_context, document.root())
+            .map_err(|e| format!("cannot extract `{}`: {}", name, e))?;
+        let value = match (selector.output, value) {
+            (XmlOutput::String, value) => Value::String(value.string()),
+            (XmlOutput::Number, value) => number(value.number()),
+            (XmlOutput::Auto, XPathValue::Boolean(value)) => Value::Bool(value),
+            (XmlOutput::Auto, XPathValue::Number(value)) => number(value),
+            (XmlOutput::Auto, XPathValue::String(value)) => Value::String(value),
+            (XmlOutput::Auto | XmlOutput::Nodes, XPathValue::Nodeset(nodes)) => {
+                Value::Array(nodes.document_order().iter().map(|node| Value::String(node.string_value())).collect())
+            }
+            (XmlOutput::Xml, XPathValue::Nodeset(nodes)) => {
+                Value::Array(nodes.document_order().iter().map(|node| Value::String(write_node(*node, &[]))).collect())
+            }
+            (XmlOutput::Nodes | XmlOutput::Xml, _) => {
+                return Err(format!("cannot extract `{}`, `{}` doesn't select nodes", name, selector.xpath).into());
+            }
+        };
+        output.insert(name.to_owned(), value);
+    }
+    Ok(Value::Object(output))
+}
+
+fn parse_csv(csv: &CsvParse, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+    let text = text(&csv.input, context)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(csv.delimiter)
+        .quote(csv.quote.unwrap_or(b'"'))
+        .quoting(csv.quote.is_some())
+        .has_headers(csv.has_header)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let columns: Vec<String> = match csv.has_header {
+        true => reader.headers()?.iter().map(|column| column.to_owned()).collect(),
+        false => csv.columns.clone(),
+    };
+    let types: HashMap<&str, ColumnType> = csv.types.iter().map(|(column, column_type)| (column.as_str(), *column_type)).collect();
+
+    let mut rows = vec![];
+    for (line, record) in reader.records().enumerate() {
+        let record = record?;
+        let mut row = Map::new();
+        for (i, cell) in record.iter().enumerate() {
+            let column = columns.get(i).cloned().unwrap_or_else(|| i.to_string());
+            let value = match types.get(column.as_str()) {
+                None | Some(ColumnType::String) => Value::String(cell.to_owned()),
+                Some(column_type) => cell_value(cell, *column_type)
+                    .ok_or_else(|| format!("row {}, column `{}`: `{}` is not a valid {}", line + 1, column, cell, column_type.as_str()))?,
+            };
+            row.insert(column, value);
+        }
+        rows.push(Value::Object(row));
+    }
+    Ok(Value::Array(rows))
+}
+
+fn cell_value(cell: &str, column_type: ColumnType) -> Option<Value> {
+    let cell = cell.trim();
+    if cell.is_empty() {
+        return Some(Value::Null);
+    }
+    match column_type {
+        ColumnType::String => Some(Value::String(cell.to_owned())),
+        ColumnType::Int => match (cell.parse::<i64>(), cell.parse::<u64>()) {
+            (Ok(int), _) => Some(Value::from(int)),
+            (_, Ok(int)) => Some(Value::from(int)),
+            _ => None,
+        },
+        ColumnType::Float => cell.parse::<f64>().ok().and_then(serde_json::Number::from_f64).map(Value::Number),
+        ColumnType::Bool => match cell.to_lowercase().as_str() {
+            "true" | "yes" | "1" => Some(Value::Bool(true)),
+            "false" | "no" | "0" => Some(Value::Bool(false)),
+            _ => None,
+        },
+    }
+}
+
+pub fn build_regex(pattern: &str, flags: &str) -> Result<Regex, String> {
+    let mut builder = RegexBuilder::new(pattern);
+    for flag in flags.chars() {
+        match flag {
+            'i' => builder.case_insensitive(true),
+            'm' => builder.multi_line(true),
+            's' => builder.dot_matches_new_line(true),
+            'x' => builder.ignore_whitespace(true),
+            _ => return Err(format!("unknown regex flag `{}`, expected i, m, s or x", flag)),
+        };
+    }
+    builder.build().map_err(|e| format!("invalid regular expression: {}", e))
+}
+
+fn parse_regex(parse: &RegexParse, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+    let regex = &parse.regex;
+    let text = text(&parse.input, context)?;
+    let names: Vec<&str> = regex.capture_names().flatten().collect();
+    let to_value = |captures: Captures| match names.is_empty() {
+        true => Value::String(captures[0].to_owned()),
+        false => {
+            let mut groups = Map::new();
+            for name in &names {
+                let value = captures.name(name).map_or(Value::Null, |group| Value::String(group.as_str().to_owned()));
+                groups.insert(name.to_string(), value);
+            }
+            Value::Object(groups)
+        }
+    };
+
+    match parse.all {
+        true => Ok(Value::Array(regex.captures_iter(&text).map(to_value).collect())),
+        false => match regex.captures(&text) {
+            None => Err(format!("`{}` doesn't match", parse.pattern).into()),
+            Some(captures) => Ok(to_value(captures)),
+        },
+    }
+}
+
+fn parse_table(table: &TableParse, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+    let html = Html::parse_document(&text(&table.input, context)?);
+    let selector = Selector::parse(&table.selector).map_err(|_| format!("`{}` is not a valid CSS selector", table.selector))?;
+    let element = html
+        .select(&selector)
+        .next()
+        .ok_or_else(|| format!("no table matches `{}`", table.selector))?;
+
+    let rows = html::table_rows(element);
+    let grid = table_grid(&rows);
+    // Header rows are the ones in <thead>, or a first row of only <th> cells.
+    let header_rows = match rows.iter().take_while(|row| row.in_head).count() {
+        0 => match rows.first() {
+            Some(row) if !row.cells.is_empty() && row.cells.iter().all(|cell| cell.value().name() == "th") => 1,
+            _ => 0,
+        },
+        count => count,
+    };
+
+    let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut names: Vec<String> = vec![];
+    let mut keys: Vec<String> = vec![];
+    for column in 0..width {
+        let key = match header_rows {
+            0 => String::new(),
+            _ => grid[header_rows - 1].get(column).cloned().flatten().unwrap_or_default(),
+        };
+        let key = match key.is_empty() {
+            true => column.to_string(),
+            false => key,
+        };
+        // Header cells spanning several columns repeat their text, later columns get a suffix.
+        let duplicates = names.iter().filter(|name| **name == key).count();
+        keys.push(match duplicates {
+            0 => key.clone(),
+            n => format!("{}_{}", key, n + 1),
+        });
+        names.push(key);
+    }
+
+    let mut records = vec![];
+    for row in grid.iter().skip(header_rows) {
+        let mut record = Map::new();
+        for (column, key) in keys.iter().enumerate() {
+            let value = row.get(column).cloned().flatten().map_or(Value::Null, Value::String);
+            record.insert(key.to_owned(), value);
+        }
+        records.push(Value::Object(record));
+    }
+    Ok(Value::Array(records))
+}
+
+fn parse_text(parse: &TextParse, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+    let document = Html::parse_document(&text(&parse.input, context)?);
+    let elements: Vec<ElementRef> = match &parse.selector {
+        None => vec![document.root_element()],
+        Some(selector) => {
+            let css = Selector::parse(selector).map_err(|_| format!("`{}` is not a valid CSS selector", selector))?;
+            document.select(&css).collect()
+        }
+    };
+    if elements.is_empty() {
+        return Err(format!("no element matches `{}`", parse.selector.as_deref().unwrap_or_default()).into());
+    }
+    Ok(Value::String(html::render(&elements, parse.format)))
+}
+
+/// Text of every cell position, with `colspan` and `rowspan` cells repeated over the positions they cover.
+fn table_grid(rows: &[TableRow]) -> Vec<Vec<Option<String>>> {
+    let mut grid: Vec<Vec<Option<String>>> = vec![vec![]; rows.len()];
+    for (y, row) in rows.iter().enumerate() {
+        let mut x = 0;
+        for cell in &row.cells {
+            // Skip positions already taken by a rowspan from above.
+            while grid[y].get(x).is_some_and(|taken| taken.is_some()) {
+                x += 1;
+            }
+            let text = cell_text(*cell);
+            let colspan = span(*cell, "colspan");
+            let rowspan = span(*cell, "rowspan");
+            for dy in 0..rowspan.min(rows.len() - y) {
+                let cells = &mut grid[y + dy];
+                if cells.len() < x + colspan {
+                    cells.resize(x + colspan, None);
+                }
+                for position in cells.iter_mut().skip(x).take(colspan) {
+                    *position = Some(text.clone());
+                }
+            }
+            x += colspan;
+        }
+    }
+    grid
+}
+
+fn span(cell: ElementRef, attribute: &str) -> usize {
+    cell.value()
+        .attr(attribute)
+        .and_then(|span| span.trim().parse::<usize>().ok())
+        .filter(|span| *span > 0)
+        .unwrap_or(1)
+        .min(1000)
+}
+
+fn cell_text(cell: ElementRef) -> String {
+    cell.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whole numbers become JSON integers, `NaN` becomes `null`.
+fn number(value: f64) -> Value {
+    match value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        true => Value::from(value as i64),
+        false => serde_json::Number::from_f64(value).map_or(Value::Null, Value::Number),
+    }
+}
+
+/// Namespace prefix and uri declared in the output, `None` binds the default namespace and an empty uri undeclares it.
+type Binding = (Option<String>, String);
+
+/// Serializes a selected node as a standalone XML fragment. `scope` holds what the enclosing output declared,
+/// every other namespace an element or attribute uses is declared on it.
+fn write_node(node: Node, scope: &[Binding]) -> String {
+    match node {
+        Node::Root(root) => root.children().into_iter().map(|child| write_node(child.into(), scope)).collect(),
+        Node::Element(element) => {
+            let mut scope = scope.to_vec();
+            let mut declarations = String::new();
+            let qname = element.name();
+            let tag = match (qname.namespace_uri(), element.preferred_prefix()) {
+                (Some(uri), Some(prefix)) => {
+                    declare(&mut scope, &mut declarations, Some(prefix), uri);
+                    format!("{}:{}", prefix, qname.local_part())
+                }
+                (Some(uri), None) => {
+                    declare(&mut scope, &mut declarations, None, uri);
+                    qname.local_part().to_owned()
+                }
+                // An element without a namespace inside a default namespace has to undeclare it.
+                (None, _) => {
+                    if bound(&scope, None).is_some_and(|uri| !uri.is_empty()) {
+                        declare(&mut scope, &mut declarations, None, "");
+                    }
+                    qname.local_part().to_owned()
+                }
+            };
+
+            let mut attributes = String::new();
+            for attribute in element.attributes() {
+                let name = attribute.name();
+                let name = match name.namespace_uri() {
+                    None => name.local_part().to_owned(),
+                    // `xml` is bound by the XML spec itself and is never declared.
+                    Some(_) if attribute.preferred_prefix() == Some("xml") => format!("xml:{}", name.local_part()),
+                    // Unprefixed attributes are in no namespace, so namespaced ones always need a prefix.
+                    Some(uri) => {
+                        let prefix = attribute
+                            .preferred_prefix()
+                            .or_else(|| element.prefix_for_namespace_uri(uri, None))
+                            .filter(|prefix| bound(&scope, Some(prefix)).is_none_or(|bound| bound == uri))
+                            .map(|prefix| prefix.to_owned())
+                            .unwrap_or_else(|| format!("ns{}", scope.len()));
+                        declare(&mut scope, &mut declarations, Some(&prefix), uri);
+                        format!("{}:{}", prefix, name.local_part())
+                    }
+                };
+                attributes.push_str(&format!(" {}=\"{}\"", name, escape_xml(attribute.value())));
+            }
+
+            let mut xml = format!("<{}{}{}", tag, declarations, attributes);
+            let children = element.children();
+            match children.is_empty() {
+                true => xml.push_str("/>"),
+                false => {
+                    xml.push('>');
+                    for child in children {
+                        xml.push_str(&write_node(child.into(), &scope));
+                    }
+                    xml.push_str(&format!("</{}>", tag));
+                }
+            }
+            xml
+        }
+        Node::Text(text) => escape_xml(text.text()),
+        Node::Attribute(attribute) => escape_xml(attribute.value()),
+        Node::Comment(comment) => format!("<!--{}-->", comment.text()),
+        Node::ProcessingInstruction(instruction) => match instruction.value() {
+            None => format!("<?{}?>", instruction.target()),
+            Some(value) => format!("<?{} {}?>", instruction.target(), value),
+        },
+        Node::Namespace(namespace) => namespace.uri().to_owned(),
+    }
+}
+
+/// Uri the prefix is bound to in the output, the innermost binding wins.
+fn bound<'a>(scope: &'a [Binding], prefix: Option<&str>) -> Option<&'a str> {
+    scope.iter().rev().find(|(bound, _)| bound.as_deref() == prefix).map(|(_, uri)| uri.as_str())
+}
+
+/// Adds an `xmlns` declaration unless the prefix is already bound to the uri.
+fn declare(scope: &mut Vec<Binding>, declarations: &mut String, prefix: Option<&str>, uri: &str) {
+    if bound(scope, prefix) == Some(uri) {
+        return;
+    }
+    match prefix {
+        None => declarations.push_str(&format!(" xmlns=\"{}\"", escape_xml(uri))),
+        Some(prefix) => declarations.push_str(&format!(" xmlns:{}=\"{}\"", prefix, escape_xml(uri))),
+    }
+    scope.push((prefix.map(|prefix| prefix.to_owned()), uri.to_owned()));
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::StepDefinition;
+    use crate::serializer;
+
+    /// Runs a parse step written the way it is in a step file.
+    fn parse_with(definition: Value, context: &ExecutionContext) -> Result<Value, Box<dyn Error>> {
+        let step = serializer::parse_step(&json!({
+            "uuid": "07124d66-f345-4c0b-90cc-5d45efbb0891",
+            "name": "parse",
+            "type": "parse",
+            "createdAt": "2022-01-30T14:40:46.559Z",
+            "errorOnFail": "Failed to parse",
+            "next": "",
+            "parse": definition,
+        }))?;
+        match step.definition {
+            StepDefinition::Parse(parse) => run(&parse.parse_type, context),
+            definition => panic!("{:?} is not a parse step", definition),
+        }
+    }
+
+    fn parse(definition: Value) -> Result<Value, Box<dyn Error>> {
+        parse_with(definition, &ExecutionContext::new())
+    }
+
+    const ITEMS: &str = r#"{"items": [{"title": "a", "price": 1}, {"title": "b", "price": 2}]}"#;
+
+    #[test]
+    fn json_pointers_must_exist() {
+        let output = parse(json!({"type": "json", "content": ITEMS, "extract": {"first": "/items/0/title"}})).unwrap();
+        assert_eq!(output, json!({"first": "a"}));
+
+        let error = parse(json!({"type": "json", "content": ITEMS, "extract": {"third": {"pointer": "/items/2/title"}}})).unwrap_err();
+        assert_eq!(error.to_string(), "cannot extract `third`, `/items/2/title` is not in the document");
+    }
+
+    #[test]
+    fn json_paths_yield_an_array_of_matches() {
+        let extract = json!({"titles": "$.items[*].title", "cheap": {"path": "$.items[?@.price < 2].title"}, "none": "$.missing"});
+        let output = parse(json!({"type": "json", "content": ITEMS, "extract": extract})).unwrap();
+        assert_eq!(output, json!({"titles": ["a", "b"], "cheap": ["a"], "none": []}));
+    }
+
+    #[test]
+    fn json_without_extract_is_the_whole_document_and_sources_can_be_values() {
+        let mut context = ExecutionContext::new();
+        context.insert("fetch", serde_json::from_str(ITEMS).unwrap());
+        assert_eq!(parse_with(json!({"type": "json", "source": "fetch"}), &context).unwrap(), context.get("fetch").unwrap().clone());
+
+        let output = parse_with(json!({"type": "json", "source": "fetch.items", "extract": {"second": "/1/title"}}), &context).unwrap();
+        assert_eq!(output, json!({"second": "b"}));
+    }
+
+    const FEED: &str = r#"<feed xmlns="urn:feed" xmlns:m="urn:meta"><entry m:id="1"><title>a</title><m:price>1.5</m:price></entry><entry m:id="2"><title>b</title><m:price>2</m:price></entry></feed>"#;
+
+    fn xml(extract: Value) -> Result<Value, Box<dyn Error>> {
+        parse(json!({"type": "xml", "content": FEED, "namespaces": {"f": "urn:feed", "meta": "urn:meta"}, "extract": extract}))
+    }
+
+    #[test]
+    fn xpath_results_keep_their_type_by_default() {
+        let extract = json!({"titles": "//f:title", "count": "count(//f:entry)", "any": "boolean(//meta:price)", "first": "string(//f:title)"});
+        assert_eq!(xml(extract).unwrap(), json!({"titles": ["a", "b"], "count": 2, "any": true, "first": "a"}));
+    }
+
+    #[test]
+    fn xpath_as_converts_the_result() {
+        let extract = json!({
+            "title": {"xpath": "//f:entry[2]/f:title", "as": "string"},
+            "total": {"xpath": "sum(//meta:price)", "as": "number"},
+            "price": {"xpath": "//meta:price", "as": "number"},
+            "ids": {"xpath": "//f:entry/@meta:id", "as": "nodes"},
+        });
+        assert_eq!(xml(extract).unwrap(), json!({"title": "b", "total": 3.5, "price": 1.5, "ids": ["1", "2"]}));
+    }
+
+    #[test]
+    fn xpath_as_xml_declares_the_namespaces_of_each_fragment() {
+        let output = xml(json!({"prices": {"xpath": "//meta:price", "as": "xml"}, "entry": {"xpath": "//f:entry[1]", "as": "xml"}})).unwrap();
+        assert_eq!(output, json!({
+            "prices": [r#"<m:price xmlns:m="urn:meta">1.5</m:price>"#, r#"<m:price xmlns:m="urn:meta">2</m:price>"#],
+            "entry": [r#"<entry xmlns="urn:feed" xmlns:m="urn:meta" m:id="1"><title>a</title><m:price>1.5</m:price></entry>"#],
+        }));
+    }
+
+    #[test]
+    fn xpath_nodes_and_xml_need_a_node_set() {
+        let error = xml(json!({"count": {"xpath": "count(//f:entry)", "as": "nodes"}})).unwrap_err();
+        assert_eq!(error.to_string(), "cannot extract `count`, `count(//f:entry)` doesn't select nodes");
+    }
+
+    #[test]
+    fn csv_header_names_the_columns() {
+        let output = parse(json!({"type": "csv", "content": "title,price\na,1\nb,2"})).unwrap();
+        assert_eq!(output, json!([{"title": "a", "price": "1"}, {"title": "b", "price": "2"}]));
+    }
+
+    #[test]
+    fn csv_columns_name_a_file_without_header() {
+        let output = parse(json!({"type": "csv", "content": "a;1;x\nb;2", "header": false, "delimiter": ";", "columns": ["title", "price"]})).unwrap();
+        assert_eq!(output, json!([{"title": "a", "price": "1", "2": "x"}, {"title": "b", "price": "2"}]));
+
+        let output = parse(json!({"type": "csv", "content": "a\tb", "header": false, "delimiter": "tab"})).unwrap();
+        assert_eq!(output, json!([{"0": "a", "1": "b"}]));
+    }
+
+    #[test]
+    fn csv_columns_with_a_header_are_rejected() {
+        let error = parse(json!({"type": "csv", "content": "title\na", "columns": ["name"]})).unwrap_err();
+        assert_eq!(error.downcast_ref::<serializer::StepError>().unwrap().field, "parse.columns");
+    }
+
+    #[test]
+    fn csv_types_convert_cells_and_empty_cells_become_null() {
+        let types = json!({"count": "int", "price": "float", "sold": "bool"});
+        let output = parse(json!({"type": "csv", "content": "title,count,price,sold\na,3,1.5,yes\n,,,", "types": types})).unwrap();
+        assert_eq!(output, json!([
+            {"title": "a", "count": 3, "price": 1.5, "sold": true},
+            {"title": "", "count": null, "price": null, "sold": null},
+        ]));
+
+        let error = parse(json!({"type": "csv", "content": "count\n3\nmany", "types": {"count": "int"}})).unwrap_err();
+        assert_eq!(error.to_string(), "row 2, column `count`: `many` is not a valid int");
+    }
+
+    const PRICES: &str = "Apple: 1.50 EUR\nPear: 2 USD\nplum: 0.80";
+
+    #[test]
+    fn regex_named_groups_become_keys() {
+        let pattern = r"(?P<fruit>\w+): (?P<price>[\d.]+)(?: (?P<currency>[A-Z]{3}))?";
+        let output = parse(json!({"type": "regex", "content": PRICES, "pattern": pattern})).unwrap();
+        assert_eq!(output, json!({"fruit": "Apple", "price": "1.50", "currency": "EUR"}));
+
+        let output = parse(json!({"type": "regex", "content": PRICES, "pattern": pattern, "all": true})).unwrap();
+        assert_eq!(output, json!([
+            {"fruit": "Apple", "price": "1.50", "currency": "EUR"},
+            {"fruit": "Pear", "price": "2", "currency": "USD"},
+            {"fruit": "plum", "price": "0.80", "currency": null},
+        ]));
+    }
+
+    #[test]
+    fn regex_without_groups_outputs_the_matched_text() {
+        let output = parse(json!({"type": "regex", "content": PRICES, "pattern": "^p\\w+", "flags": "im", "all": true})).unwrap();
+        assert_eq!(output, json!(["Pear", "plum"]));
+
+        let output = parse(json!({"type": "regex", "content": PRICES, "pattern": "[A-Z]{3}"})).unwrap();
+        assert_eq!(output, json!("EUR"));
+    }
+
+    #[test]
+    fn regex_without_a_match_is_an_error_unless_all_is_set() {
+        let error = parse(json!({"type": "regex", "content": PRICES, "pattern": "GBP"})).unwrap_err();
+        assert_eq!(error.to_string(), "`GBP` doesn't match");
+        assert_eq!(parse(json!({"type": "regex", "content": PRICES, "pattern": "GBP", "all": true})).unwrap(), json!([]));
+    }
+
+    fn table(html: &str) -> Value {
+        parse(json!({"type": "table", "content": html})).unwrap()
+    }
+
+    #[test]
+    fn table_spans_repeat_their_text() {
+        let html = "<table><tr><th>Fruit</th><th>Size</th><th>Price</th></tr>\
+            <tr><td rowspan=\"2\">Apple</td><td colspan=\"2\">sold out</td></tr>\
+            <tr><td>L</td><td>2</td></tr></table>";
+        assert_eq!(table(html), json!([
+            {"Fruit": "Apple", "Size": "sold out", "Price": "sold out"},
+            {"Fruit": "Apple", "Size": "L", "Price": "2"},
+        ]));
+    }
+
+    #[test]
+    fn table_header_comes_from_thead_or_a_first_row_of_th() {
+        // The last <thead> row names the columns, a header spanning several gets numbered suffixes.
+        let html = "<table><thead><tr><th colspan=\"3\">Fruits</th></tr><tr><th>Name</th><th colspan=\"2\">Price</th></tr></thead>\
+            <tbody><tr><td>Apple</td><td>1</td><td>EUR</td></tr></tbody></table>";
+        assert_eq!(table(html), json!([{"Name": "Apple", "Price": "1", "Price_2": "EUR"}]));
+
+        let html = "<table><tr><th>Name</th><td>Price</td></tr><tr><td>Apple</td><td>1</td></tr></table>";
+        assert_eq!(table(html), json!([{"0": "Name", "1": "Price"}, {"0": "Apple", "1": "1"}]));
+    }
+
+    #[test]
+    fn table_missing_cells_are_null() {
+        let html = "<table><tr><th>Name</th><th>Price</th><th></th></tr><tr><td>Apple</td></tr><tr><td>Pear</td><td>2</td><td>x</td></tr></table>";
+        assert_eq!(table(html), json!([
+            {"Name": "Apple", "Price": null, "2": null},
+            {"Name": "Pear", "Price": "2", "2": "x"},
+        ]));
+    }
+
+    #[test]
+    fn table_selector_picks_the_table() {
+        let html = "<table><tr><td>skip</td></tr></table><table id=\"prices\"><tr><th>Name</th></tr><tr><td>Apple</td></tr></table>";
+        let output = parse(json!({"type": "table", "content": html, "selector": "#prices"})).unwrap();
+        assert_eq!(output, json!([{"Name": "Apple"}]));
+
+        let error = parse(json!({"type": "table", "content": html, "selector": "#missing"})).unwrap_err();
+        assert_eq!(error.to_string(), "no table matches `#missing`");
+    }
+
+    #[test]
+    fn text_selector_renders_every_match_or_fails_without_one() {
+        let html = "<div class=\"item\"><h2>Apple</h2></div><p>skip</p><div class=\"item\"><h2>Pear</h2></div>";
+        let output = parse(json!({"type": "text", "content": html, "selector": ".item", "format": "markdown"})).unwrap();
+        assert_eq!(output, json!("## Apple\n\n## Pear"));
+
+        let error = parse(json!({"type": "text", "content": html, "selector": ".missing"})).unwrap_err();
+        assert_eq!(error.to_string(), "no element matches `.missing`");
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::http::{HttpClient, HttpRequest, HttpResponse};
+use crate::models::RateLimit;
+
+/// Time source of the limiter, replaced by a fake clock to check the throttling without waiting.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+#[derive(Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+#[derive(Debug, Default)]
+struct HostState {
+    /// Earliest time the next request may start.
+    next_slot: Option<Instant>,
+    in_flight: u32,
+}
+
+/// Throttles requests per host. Requests are spaced `1 / requests_per_second` apart and at most
+/// `max_concurrent` of them are in flight at a time.
+#[derive(Debug)]
+pub struct HostLimiter {
+    defaults: RateLimit,
+    clock: Box<dyn Clock>,
+    hosts: Mutex<HashMap<String, HostState>>,
+    released: Condvar,
+}
+
+impl HostLimiter {
+    pub fn new(defaults: RateLimit, clock: Box<dyn Clock>) -> Self {
+        HostLimiter {
+            defaults,
+            clock,
+            hosts: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Blocks until a request to `host` is allowed. The request counts as in flight until the permit is dropped.
+    pub fn acquire(&self, host: &str, rate_limit: &RateLimit) -> Permit<'_> {
+        let rate_limit = rate_limit.or(&self.defaults);
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(max_concurrent) = rate_limit.max_concurrent {
+            while hosts.get(host).map_or(0, |state| state.in_flight) >= max_concurrent {
+                hosts = self.released.wait(hosts).unwrap();
+            }
+        }
+
+        let state = hosts.entry(host.to_owned()).or_default();
+        state.in_flight += 1;
+        // The slot is reserved before sleeping, so requests waiting at the same time queue up behind each other.
+        let wait = match rate_limit.requests_per_second {
+            None => Duration::ZERO,
+            Some(requests_per_second) => {
+                let now = self.clock.now();
+                let slot = state.next_slot.map_or(now, |next_slot| next_slot.max(now));
+                state.next_slot = Some(slot + Duration::from_secs_f64(1.0 / requests_per_second));
+                slot - now
+            }
+        };
+        drop(hosts);
+
+        if !wait.is_zero() {
+            self.clock.sleep(wait);
+        }
+        Permit {
+            limiter: self,
+            host: host.to_owned(),
+        }
+    }
+}
+
+pub struct Permit<'a> {
+    limiter: &'a HostLimiter,
+    host: String,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut hosts = self.limiter.hosts.lock().unwrap();
+        if let Some(state) = hosts.get_mut(&self.host) {
+            state.in_flight -= 1;
+        }
+        self.limiter.released.notify_all();
+    }
+}
+
+/// Sends the requests of another client through a [`HostLimiter`].
+#[derive(Debug)]
+pub struct RateLimitedClient {
+    inner: Box<dyn HttpClient>,
+    limiter: HostLimiter,
+}
+
+impl RateLimitedClient {
+    pub fn new(inner: Box<dyn HttpClient>, limiter: HostLimiter) -> Self {
+        RateLimitedClient {
+            inner,
+            limiter,
+        }
+    }
+}
+
+impl HttpClient for RateLimitedClient {
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Box<dyn Error>> {
+        let url = Url::parse(&request.url)?;
+        let host = match (url.host_str(), url.port()) {
+            (None, _) => return Err(format!("{} has no host", request.url).into()),
+            (Some(host), None) => host.to_owned(),
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+        };
+        let _permit = self.limiter.acquire(&host, &request.rate_limit);
+        self.inner.send(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Clock that only moves when it is advanced or slept on. Sleeping returns at once and records the duration.
+    #[derive(Debug, Clone)]
+    struct FakeClock(Arc<FakeTime>);
+
+    #[derive(Debug)]
+    struct FakeTime {
+        start: Instant,
+        elapsed: Mutex<Duration>,
+        sleeps: Mutex<Vec<Duration>>,
+    }
+
+    impl FakeClock {
+        fn new() -> Self {
+            FakeClock(Arc::new(FakeTime {
+                start: Instant::now(),
+                elapsed: Mutex::new(Duration::ZERO),
+                sleeps: Mutex::new(vec![]),
+            }))
+        }
+
+        fn advance(&self, duration: Duration) {
+            *self.0.elapsed.lock().unwrap() += duration;
+        }
+
+        fn sleeps(&self) -> Vec<Duration> {
+            self.0.sleeps.lock().unwrap().clone()
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.0.start + *self.0.elapsed.lock().unwrap()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.0.sleeps.lock().unwrap().push(duration);
+            self.advance(duration);
+        }
+    }
+
+    fn limiter(defaults: RateLimit) -> (HostLimiter, FakeClock) {
+        let clock = FakeClock::new();
+        (HostLimiter::new(defaults, Box::new(clock.clone())), clock)
+    }
+
+    fn per_second(requests_per_second: f64) -> RateLimit {
+        RateLimit {
+            requests_per_second: Some(requests_per_second),
+            max_concurrent: None,
+        }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn requests_to_a_host_are_spaced() {
+        let (limiter, clock) = limiter(per_second(2.0));
+        for _ in 0..3 {
+            limiter.acquire("example.com", &RateLimit::default());
+        }
+        assert_eq!(clock.sleeps(), [ms(500), ms(500)]);
+
+        // Once the host has been idle long enough the next request goes out at once.
+        clock.advance(ms(2_000));
+        limiter.acquire("example.com", &RateLimit::default());
+        assert_eq!(clock.sleeps().len(), 2);
+    }
+
+    #[test]
+    fn step_rate_limit_overrides_the_global_one() {
+        let (limiter, clock) = limiter(per_second(1.0));
+        for _ in 0..3 {
+            limiter.acquire("example.com", &per_second(4.0));
+        }
+        assert_eq!(clock.sleeps(), [ms(250), ms(250)]);
+    }
+
+    #[test]
+    fn hosts_do_not_throttle_each_other() {
+        let (limiter, clock) = limiter(per_second(1.0));
+        limiter.acquire("example.com", &RateLimit::default());
+        limiter.acquire("example.org", &RateLimit::default());
+        limiter.acquire("example.com:8080", &RateLimit::default());
+        assert!(clock.sleeps().is_empty());
+    }
+
+    #[test]
+    fn max_concurrent_blocks_until_a_permit_is_released() {
+        let (limiter, _) = limiter(RateLimit {
+            requests_per_second: None,
+            max_concurrent: Some(1),
+        });
+        let first = limiter.acquire("example.com", &RateLimit::default());
+        // Another host is not held up by the request in flight.
+        drop(limiter.acquire("example.org", &RateLimit::default()));
+
+        let (acquired, received) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let _second = limiter.acquire("example.com", &RateLimit::default());
+                acquired.send(()).unwrap();
+            });
+            assert!(received.recv_timeout(ms(200)).is_err(), "second request started while the first was in flight");
+            drop(first);
+            assert!(received.recv_timeout(Duration::from_secs(5)).is_ok(), "second request never started");
+        });
+    }
+}
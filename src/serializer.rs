@@ -1,190 +1,925 @@
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
 use serde::{
     Deserialize, Deserializer,
 };
-use serde_json;
+use chrono::format::{Item, StrftimeItems};
+use chrono::Locale;
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use scraper::Selector;
+use serde_json_path::JsonPath;
+use crate::{convert, parse};
 use crate::{
+    ColumnType,
     ConvertDefinition,
-    ConvertOption,
+    ConvertSource,
+    ConvertType,
+    CsvParse,
+    HttpAuth,
+    HttpBody,
+    HttpCache,
     HttpDefinition,
+    HttpExpect,
+    HttpExtract,
+    HttpLimits,
     HttpType,
+    JsonParse,
+    JsonSelector,
+    MultipartField,
     ParseDefinition,
+    ParseInput,
     ParseType,
+    XPathSelector,
+    XmlOutput,
+    XmlParse,
+    RateLimit,
+    RegexParse,
     Step,
     StepDefinition,
+    TableParse,
+    TextFormat,
+    TextParse,
 };
 
+const NAME: &str = "name";
+const HTTP: &str = "http";
+const PARSE: &str = "parse";
+const CONVERT: &str = "convert";
+const UUID: &str = "uuid";
+const WORKFLOW_ID: &str = "workflowId";
+const TYPE: &str = "type";
+const NEXT: &str = "next";
+const RETRIES: &str = "retries";
+const ERROR_ON_FAIL: &str = "errorOnFail";
+const CREATED_AT: &str = "createdAt";
+
+/// Invalid step definition. `field` is the dotted path of the offending field, e.g. `convert.source`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StepError {
+    pub field: String,
+    pub message: String,
+}
+
+impl StepError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        StepError {
+            field: field.to_owned(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for StepError {}
+
 impl<'de> Deserialize<'de> for Step {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where
             D: Deserializer<'de>,
     {
-        const NAME: &str = "name";
-        const HTTP: &str = "http";
-        const PARSE: &str = "parse";
-        const CONVERT: &str = "convert";
-        const UUID: &str = "uuid";
-        const TYPE: &str = "type";
-        const NEXT: &str = "next";
-        const ERROR_ON_FAIL: &str = "errorOnFail";
-        const CREATED_AT: &str = "createdAt";
-
-        let json: serde_json::value::Value = serde_json::value::Value::deserialize(deserializer)?;
-        let typ = json.get(TYPE).expect(TYPE).as_str().unwrap();
-        let uuid = json.get(UUID).expect(UUID).as_str().unwrap();
-        let name = json.get(NAME).expect(NAME).as_str().unwrap();
-        let error_on_fail = json.get(ERROR_ON_FAIL).expect(ERROR_ON_FAIL).as_str().unwrap();
-        let next: Option<String> = match json.get(NEXT).expect(NEXT).as_str() {
-            None => None,
-            Some(some) => {
-                Some(some.to_owned())
+        let json: Value = Value::deserialize(deserializer)?;
+        parse_step(&json).map_err(serde::de::Error::custom)
+    }
+}
+
+pub fn parse_step(json: &Value) -> Result<Step, StepError> {
+    let typ = required_str(json, TYPE, TYPE)?;
+    let uuid = required_str(json, UUID, UUID)?;
+    let name = required_str(json, NAME, NAME)?;
+    let error_on_fail = required_str(json, ERROR_ON_FAIL, ERROR_ON_FAIL)?;
+    let next: Option<String> = json.get(NEXT).and_then(|next| next.as_str()).map(|some| some.to_owned());
+    let retries = match json.get(RETRIES).filter(|retries| !retries.is_null()) {
+        None => 0,
+        Some(retries) => match retries.as_u64() {
+            None => {
+                return Err(StepError::new(RETRIES, "retries must be a non-negative integer"));
             }
-        };
-        let created_at = json.get(CREATED_AT).expect(CREATED_AT).as_str().unwrap();
-
-        let definition = match typ {
-            CONVERT => {
-                let convert = json.get(CONVERT);
-                match convert {
-                    None => {
-                        return Err(serde::de::Error::custom("missing convert object"));
-                    }
-                    Some(convert) => {
-                        let from = convert.get("from").expect("from").as_str().unwrap();
-                        let to = convert.get("to").expect("to").as_str().unwrap();
+            Some(retries) => match u32::try_from(retries) {
+                Err(_) => return Err(StepError::new(RETRIES, "retries must be between 0 and 4294967295")),
+                Ok(retries) => retries,
+            },
+        },
+    };
+    let workflow_id: Option<String> = json.get(WORKFLOW_ID).and_then(|id| id.as_str()).map(|id| id.to_owned());
+    let created_at = required_str(json, CREATED_AT, CREATED_AT)?;
 
-                        match (from, to) {
-                            ("string", "string") => {
-                                return Err(serde::de::Error::custom("cannot convert from string to string"));
+    let definition = match typ {
+        CONVERT => {
+            let convert = json.get(CONVERT);
+            match convert {
+                None => {
+                    return Err(StepError::new(CONVERT, "missing convert object"));
+                }
+                Some(convert) => {
+                    let from = convert_type(convert, "from")?;
+                    let to = convert_type(convert, "to")?;
+                    if !from.converts_to(to) {
+                        return Err(StepError::new("convert.to", format!("cannot convert from {} to {}", from.as_str(), to.as_str())));
+                    }
+                    let source = convert_source(required(convert, "source", "convert.source")?);
+                    // Context values and rendered templates are only known when the step runs.
+                    if let ConvertSource::Literal(source) = &source {
+                        let expected = match from {
+                            ConvertType::String | ConvertType::Base64 | ConvertType::Hex if !source.is_string() => Some("a string"),
+                            ConvertType::Int if source.is_i64() || source.is_u64() => None,
+                            ConvertType::Int if is_integer(source) => {
+                                return Err(StepError::new("convert.source", "convert –» source overflows a 64-bit integer"));
                             }
-                            ("string", "int") => {
-                                let source = convert.get("source").expect("source").as_str().unwrap();
-                                StepDefinition::Convert(ConvertDefinition {
-                                    source: ConvertOption::FromStringToInt(source.to_owned())
-                                })
+                            ConvertType::Int => {
+                                return Err(StepError::new("convert.source", "Couldn't parse [convert –» source] to integer. Maybe you wanted to parse from string to integer?"));
                             }
-                            ("int", "string") => {
-                                let source = convert.get("source").expect("source").as_i64();
-                                match source {
-                                    None => {
-                                        return Err(serde::de::Error::custom("Couldn't parse [convert –» source] to integer. Maybe you wanted to parse from string to integer?"));
-                                    }
-                                    Some(source) => {
-                                        StepDefinition::Convert(ConvertDefinition {
-                                            source: ConvertOption::FromIntToString(source as i32)
-                                        })
-                                    }
-                                }
+                            ConvertType::Float if !source.is_number() => Some("a number"),
+                            ConvertType::Bool if !source.is_boolean() => Some("a boolean"),
+                            ConvertType::Decimal if !source.is_string() && !source.is_number() => Some("a string or a number"),
+                            ConvertType::Decimal if source.is_number() && Decimal::from_str_exact(&source.to_string()).or_else(|_| Decimal::from_scientific(&source.to_string())).is_err() => {
+                                return Err(StepError::new("convert.source", "convert –» source has more digits than a decimal holds (28)"));
+                            }
+                            ConvertType::DateTime if !source.is_string() && !source.is_i64() => Some("a string or a unix timestamp"),
+                            _ => None,
+                        };
+                        if let Some(expected) = expected {
+                            return Err(StepError::new("convert.source", format!("convert –» source is not {}", expected)));
+                        }
+                    }
+                    let target = match optional_str(convert.get("target"), "convert.target")? {
+                        Some(target) if target.is_empty() || target.contains('.') => {
+                            return Err(StepError::new("convert.target", "target must be a non-empty key without dots"));
+                        }
+                        target => target,
+                    };
+                    let timezone = match optional_str(convert.get("timezone"), "convert.timezone")? {
+                        None => None,
+                        Some(timezone) => match timezone.parse::<Tz>() {
+                            Ok(timezone) => Some(timezone),
+                            Err(_) => {
+                                return Err(StepError::new("convert.timezone", format!("`{}` is not a known time zone", timezone)));
                             }
-                            ("int", "int") => {
-                                return Err(serde::de::Error::custom("cannot convert from integer to integer"));
+                        },
+                    };
+                    let locale = match optional_str(convert.get("locale"), "convert.locale")? {
+                        None => None,
+                        Some(name) => match locale(&name) {
+                            Some(locale) => Some(locale),
+                            None => {
+                                return Err(StepError::new("convert.locale", format!("`{}` is not a known locale", name)));
                             }
-                            (_, _) => {
-                                return Err(serde::de::Error::custom("unknown convert options"));
+                        },
+                    };
+                    let decimal_separator = separator(convert, "decimalSeparator")?;
+                    let thousands_separator = match convert.get("thousandsSeparator") {
+                        Some(Value::String(separator)) if separator.is_empty() => Some(None),
+                        _ => separator(convert, "thousandsSeparator")?.map(Some),
+                    };
+                    let number_format = match (locale, decimal_separator, thousands_separator) {
+                        (None, None, None) => None,
+                        (locale, decimal_separator, thousands_separator) => {
+                            let format = convert::number_format(locale, decimal_separator, thousands_separator);
+                            if format.thousands_separator == Some(format.decimal_separator) {
+                                return Err(StepError::new("convert.thousandsSeparator", "the thousands and decimal separators are the same"));
                             }
+                            Some(format)
                         }
-                    }
+                    };
+                    StepDefinition::Convert(ConvertDefinition {
+                        from,
+                        to,
+                        source,
+                        from_format: date_format(convert, "fromFormat")?,
+                        to_format: date_format(convert, "toFormat")?,
+                        timezone,
+                        target,
+                        locale,
+                        number_format,
+                    })
                 }
             }
-            HTTP => {
-                let http = json.get(HTTP);
-                match http {
-                    None => {
-                        return Err(serde::de::Error::custom("http missing"));
-                    }
-                    Some(http) => {
-                        let url = http.get("url").expect("url").as_str().unwrap();
-                        let http_typ = http.get("type").expect("type").as_str().unwrap();
-                        match http_typ.to_lowercase().as_str() {
-                            "get" => {
-                                StepDefinition::Http(HttpDefinition {
-                                    http_type: HttpType::Get(url.to_owned())
-                                })
-                            }
-                            "post" => {
-                                let body = http.get("body");
-                                match body {
-                                    None => {
-                                        return Err(serde::de::Error::custom("Missing body for post request step."));
-                                    }
-                                    Some(body) => {
-                                        let body = body.as_str().unwrap().to_owned();
-                                        StepDefinition::Http(HttpDefinition {
-                                            http_type: HttpType::Post(url.to_owned(), body)
-                                        })
-                                    }
+        }
+        HTTP => {
+            let http = json.get(HTTP);
+            match http {
+                None => {
+                    return Err(StepError::new(HTTP, "http missing"));
+                }
+                Some(http) => {
+                    let url = required_str(http, "url", "http.url")?;
+                    let http_typ = required_str(http, "type", "http.type")?;
+                    let http_type = match http_typ.to_lowercase().as_str() {
+                        "get" => {
+                            HttpType::Get(url.to_owned())
+                        }
+                        "post" => {
+                            let body = http_body(http)?;
+                            match body {
+                                None => {
+                                    return Err(StepError::new("http.body", "Missing body for post request step."));
+                                }
+                                Some(body) => {
+                                    HttpType::Post(url.to_owned(), body)
                                 }
-                            }
-                            _ => {
-                                return Err(serde::de::Error::custom("Unknown http type. Supported types are GET and POST."));
                             }
                         }
-                    }
+                        "put" => {
+                            HttpType::Put(url.to_owned(), http_body(http)?)
+                        }
+                        "patch" => {
+                            HttpType::Patch(url.to_owned(), http_body(http)?)
+                        }
+                        "delete" => {
+                            HttpType::Delete(url.to_owned(), http_body(http)?)
+                        }
+                        "head" => {
+                            HttpType::Head(url.to_owned())
+                        }
+                        _ => {
+                            return Err(StepError::new("http.type", "Unknown http type. Supported types are GET, POST, PUT, PATCH, DELETE and HEAD."));
+                        }
+                    };
+                    let cache = http_cache(http, &http_type)?;
+                    StepDefinition::Http(Box::new(HttpDefinition {
+                        http_type,
+                        headers: string_pairs(http, "headers", "http.headers")?,
+                        query: string_pairs(http, "query", "http.query")?,
+                        auth: http_auth(http)?,
+                        expect: http_expect(http)?,
+                        extract: http_extract(http)?,
+                        limits: http_limits(http)?,
+                        rate_limit: rate_limit(http)?,
+                        cache,
+                    }))
                 }
             }
-            PARSE => {
-                let parse = json.get(PARSE);
-                match parse {
-                    None => {
-                        return Err(serde::de::Error::custom("invalid parse step"));
-                    }
-                    Some(parse) => {
-                        let type_parse = parse.get("type");
-                        let content = parse.get("content");
-                        match (type_parse, content) {
-                            (Some(type_parse), Some(content)) => {
-                                match (type_parse.as_str(), content.as_str()) {
-                                    (None, None) => {
-                                        return Err(serde::de::Error::custom("parse –» type is not a string and parse –» content is not a string"));
-                                    }
-                                    (Some(type_parse), Some(content)) => {
-                                        match type_parse {
-                                            "document" => {
-                                                StepDefinition::Parse(ParseDefinition {
-                                                    parse_type: ParseType::Document(content.to_owned())
-                                                })
-                                            }
-                                            _ => {
-                                                return Err(serde::de::Error::custom("invalid parse type"));
-                                            }
-                                        }
-                                    }
-                                    (Some(_), None) => {
-                                        return Err(serde::de::Error::custom("parse –» content is not a string"));
-                                    }
-                                    (None, Some(_)) => {
-                                        return Err(serde::de::Error::custom("parse –» type is not a string"));
-                                    }
-                                }
+        }
+        PARSE => {
+            let parse = json.get(PARSE);
+            match parse {
+                None => {
+                    return Err(StepError::new(PARSE, "invalid parse step"));
+                }
+                Some(parse) => {
+                    let type_parse = match parse.get("type") {
+                        None => {
+                            return Err(StepError::new("parse.type", "missing parse –» type"));
+                        }
+                        Some(type_parse) => match type_parse.as_str() {
+                            None => {
+                                return Err(StepError::new("parse.type", "parse –» type is not a string"));
                             }
-                            (None, Some(_)) => {
-                                return Err(serde::de::Error::custom("missing parse –» type"));
+                            Some(type_parse) => type_parse,
+                        },
+                    };
+                    let parse_type = match type_parse {
+                        "document" => {
+                            match parse.get("content") {
+                                None => {
+                                    return Err(StepError::new("parse.content", "missing parse –» content"));
+                                }
+                                Some(content) => match content.as_str() {
+                                    None => {
+                                        return Err(StepError::new("parse.content", "parse –» content is not a string"));
+                                    }
+                                    Some(content) => ParseType::Document(content.to_owned()),
+                                },
                             }
-                            (Some(_), None) => {
-                                return Err(serde::de::Error::custom("missing parse –» content"));
+                        }
+                        "json" => {
+                            ParseType::Json(JsonParse {
+                                input: parse_input(parse)?,
+                                extract: json_selectors(parse)?,
+                            })
+                        }
+                        "xml" => {
+                            ParseType::Xml(XmlParse {
+                                input: parse_input(parse)?,
+                                namespaces: string_pairs(parse, "namespaces", "parse.namespaces")?,
+                                extract: xpath_selectors(parse)?,
+                            })
+                        }
+                        "csv" => {
+                            ParseType::Csv(csv_parse(parse)?)
+                        }
+                        "regex" => {
+                            let pattern = required_str(parse, "pattern", "parse.pattern")?;
+                            let flags = optional_str(parse.get("flags"), "parse.flags")?.unwrap_or_default();
+                            let regex = match parse::build_regex(pattern, &flags) {
+                                Ok(regex) => regex,
+                                Err(e) => {
+                                    let field = match flags.chars().all(|flag| "imsx".contains(flag)) {
+                                        true => "parse.pattern",
+                                        false => "parse.flags",
+                                    };
+                                    return Err(StepError::new(field, e));
+                                }
+                            };
+                            let all = match parse.get("all") {
+                                None | Some(Value::Null) => false,
+                                Some(Value::Bool(all)) => *all,
+                                Some(_) => {
+                                    return Err(StepError::new("parse.all", "parse –» all is not a boolean"));
+                                }
+                            };
+                            ParseType::Regex(RegexParse {
+                                input: parse_input(parse)?,
+                                pattern: pattern.to_owned(),
+                                flags,
+                                regex,
+                                all,
+                            })
+                        }
+                        "table" => {
+                            let selector = optional_str(parse.get("selector"), "parse.selector")?.unwrap_or_else(|| "table".to_owned());
+                            if Selector::parse(&selector).is_err() {
+                                return Err(StepError::new("parse.selector", format!("`{}` is not a valid CSS selector", selector)));
                             }
-                            (None, None) => {
-                                return Err(serde::de::Error::custom("empty parse object"));
+                            ParseType::Table(TableParse {
+                                input: parse_input(parse)?,
+                                selector,
+                            })
+                        }
+                        "text" => {
+                            let selector = optional_str(parse.get("selector"), "parse.selector")?;
+                            if let Some(selector) = &selector {
+                                if Selector::parse(selector).is_err() {
+                                    return Err(StepError::new("parse.selector", format!("`{}` is not a valid CSS selector", selector)));
+                                }
                             }
+                            let format = match optional_str(parse.get("format"), "parse.format")?.as_deref() {
+                                None | Some("text") => TextFormat::Text,
+                                Some("markdown") => TextFormat::Markdown,
+                                Some(_) => {
+                                    return Err(StepError::new("parse.format", "`format` is one of text and markdown"));
+                                }
+                            };
+                            ParseType::Text(TextParse {
+                                input: parse_input(parse)?,
+                                selector,
+                                format,
+                            })
                         }
+                        _ => {
+                            return Err(StepError::new("parse.type", "invalid parse type"));
+                        }
+                    };
+                    StepDefinition::Parse(ParseDefinition {
+                        parse_type
+                    })
+                }
+            }
+        }
+        _ => {
+            return Err(StepError::new(TYPE, "Unknown step type"));
+        }
+    };
+
+    Ok(Step {
+        next,
+        retries,
+        name: name.to_owned(),
+        uuid: uuid.to_owned(),
+        workflow_id,
+        definition,
+        error_on_fail: error_on_fail.to_owned(),
+        created_at: created_at.to_owned(),
+    })
+}
+
+/// `{"name": "value"}` object as pairs. A value can also be a number, a boolean or an array
+/// of them, which repeats the name, e.g. for `?tag=a&tag=b`.
+fn string_pairs(http: &Value, key: &str, field: &str) -> Result<Vec<(String, String)>, StepError> {
+    let object = match http.get(key) {
+        None | Some(Value::Null) => return Ok(vec![]),
+        Some(Value::Object(object)) => object,
+        Some(_) => return Err(StepError::new(field, format!("{} is not an object", field.replace('.', " –» ")))),
+    };
+
+    let mut pairs = vec![];
+    for (name, value) in object {
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                Value::String(value) => value.to_owned(),
+                Value::Number(value) => value.to_string(),
+                Value::Bool(value) => value.to_string(),
+                _ => {
+                    return Err(StepError::new(&format!("{}.{}", field, name), "value must be a string, a number or a boolean"));
+                }
+            };
+            pairs.push((name.to_owned(), value));
+        }
+    }
+    Ok(pairs)
+}
+
+fn http_auth(http: &Value) -> Result<Option<HttpAuth>, StepError> {
+    let auth = match http.get("auth") {
+        None | Some(Value::Null) => return Ok(None),
+        Some(auth) => auth,
+    };
+    let auth_type = required_str(auth, "type", "http.auth.type")?;
+    let auth = match auth_type {
+        "bearer" => HttpAuth::Bearer(required_str(auth, "token", "http.auth.token")?.to_owned()),
+        "basic" => HttpAuth::Basic {
+            username: required_str(auth, "username", "http.auth.username")?.to_owned(),
+            password: required_str(auth, "password", "http.auth.password")?.to_owned(),
+        },
+        "apiKey" => HttpAuth::ApiKey {
+            header: required_str(auth, "header", "http.auth.header")?.to_owned(),
+            value: required_str(auth, "value", "http.auth.value")?.to_owned(),
+        },
+        _ => {
+            return Err(StepError::new("http.auth.type", "Unknown auth type. Supported types are bearer, basic and apiKey."));
+        }
+    };
+    Ok(Some(auth))
+}
+
+/// Either the literal `content` or a `source` path into the execution context.
+fn parse_input(parse: &Value) -> Result<ParseInput, StepError> {
+    match (parse.get("content"), parse.get("source")) {
+        (Some(Value::String(content)), None) => Ok(ParseInput::Content(content.to_owned())),
+        (None, Some(Value::String(source))) => Ok(ParseInput::Source(source.to_owned())),
+        (Some(_), None) => Err(StepError::new("parse.content", "parse –» content is not a string")),
+        (None, Some(_)) => Err(StepError::new("parse.source", "parse –» source is not a string")),
+        (None, None) => Err(StepError::new("parse.content", "missing parse –» content or parse –» source")),
+        (Some(_), Some(_)) => Err(StepError::new("parse.source", "only one of content and source can be set")),
+    }
+}
+
+/// `{"name": "/pointer"}`, `{"name": "$.path"}` or the explicit `{"name": {"pointer": ...}}` / `{"name": {"path": ...}}`.
+fn json_selectors(parse: &Value) -> Result<Vec<(String, JsonSelector)>, StepError> {
+    let extract = match parse.get("extract") {
+        None | Some(Value::Null) => return Ok(vec![]),
+        Some(Value::Object(extract)) => extract,
+        Some(_) => return Err(StepError::new("parse.extract", "parse –» extract is not an object")),
+    };
+
+    let mut selectors = vec![];
+    for (name, selector) in extract {
+        let field = format!("parse.extract.{}", name);
+        let selector = match (selector, selector.get("pointer"), selector.get("path")) {
+            (Value::String(expression), _, _) if expression.is_empty() || expression.starts_with('/') => JsonSelector::Pointer(expression.to_owned()),
+            (Value::String(expression), _, _) if expression.starts_with('$') => json_path(expression, &field)?,
+            (_, Some(Value::String(pointer)), None) if pointer.is_empty() || pointer.starts_with('/') => JsonSelector::Pointer(pointer.to_owned()),
+            (_, None, Some(Value::String(path))) => json_path(path, &field)?,
+            _ => return Err(StepError::new(&field, "expected a JSON pointer (`/items/0`) or a JSONPath (`$.items[*]`)")),
+        };
+        selectors.push((name.to_owned(), selector));
+    }
+    Ok(selectors)
+}
+
+fn json_path(path: &str, field: &str) -> Result<JsonSelector, StepError> {
+    match JsonPath::parse(path) {
+        Ok(path) => Ok(JsonSelector::Path(path)),
+        Err(e) => Err(StepError::new(field, format!("invalid JSONPath: {}", e))),
+    }
+}
+
+/// `{"name": "//item/title"}` or `{"name": {"xpath": "//item/title", "as": "xml"}}`.
+fn xpath_selectors(parse: &Value) -> Result<Vec<(String, XPathSelector)>, StepError> {
+    let extract = match parse.get("extract") {
+        None | Some(Value::Null) => return Err(StepError::new("parse.extract", "missing parse –» extract")),
+        Some(Value::Object(extract)) => extract,
+        Some(_) => return Err(StepError::new("parse.extract", "parse –» extract is not an object")),
+    };
+
+    let mut selectors = vec![];
+    for (name, selector) in extract {
+        let field = format!("parse.extract.{}", name);
+        let (xpath, output) = match selector {
+            Value::String(xpath) => (xpath.as_str(), XmlOutput::Auto),
+            Value::Object(_) => {
+                let output = match selector.get("as").and_then(|output| output.as_str()) {
+                    None => XmlOutput::Auto,
+                    Some("string") => XmlOutput::String,
+                    Some("number") => XmlOutput::Number,
+                    Some("nodes") => XmlOutput::Nodes,
+                    Some("xml") => XmlOutput::Xml,
+                    Some(_) => return Err(StepError::new(&format!("{}.as", field), "`as` is one of string, number, nodes and xml")),
+                };
+                (required_str(selector, "xpath", &format!("{}.xpath", field))?, output)
+            }
+            _ => return Err(StepError::new(&field, "expected an XPath or {\"xpath\": ..., \"as\": ...}")),
+        };
+        let compiled = match sxd_xpath::Factory::new().build(xpath) {
+            Ok(Some(compiled)) => compiled,
+            Ok(None) => return Err(StepError::new(&field, "empty XPath")),
+            Err(e) => return Err(StepError::new(&field, format!("invalid XPath: {}", e))),
+        };
+        selectors.push((name.to_owned(), XPathSelector {
+            xpath: xpath.to_owned(),
+            compiled: Rc::new(compiled),
+            output,
+        }));
+    }
+    Ok(selectors)
+}
+
+fn csv_parse(parse: &Value) -> Result<CsvParse, StepError> {
+    let delimiter = match parse.get("delimiter") {
+        None | Some(Value::Null) => b',',
+        Some(Value::String(delimiter)) if delimiter == "tab" => b'\t',
+        Some(Value::String(delimiter)) if delimiter.len() == 1 => delimiter.as_bytes()[0],
+        Some(_) => return Err(StepError::new("parse.delimiter", "delimiter must be a single ASCII character or `tab`")),
+    };
+    let quote = match parse.get("quote") {
+        None | Some(Value::Null) => Some(b'"'),
+        Some(Value::Bool(false)) => None,
+        Some(Value::String(quote)) if quote.len() == 1 => Some(quote.as_bytes()[0]),
+        Some(_) => return Err(StepError::new("parse.quote", "quote must be a single ASCII character or false")),
+    };
+    let has_header = match parse.get("header") {
+        None | Some(Value::Null) => true,
+        Some(Value::Bool(has_header)) => *has_header,
+        Some(_) => return Err(StepError::new("parse.header", "parse –» header is not a boolean")),
+    };
+    let columns = match parse.get("columns") {
+        None | Some(Value::Null) => vec![],
+        Some(Value::Array(columns)) => {
+            let mut names = vec![];
+            for column in columns {
+                match column.as_str() {
+                    None => return Err(StepError::new("parse.columns", "column names must be strings")),
+                    Some(column) => names.push(column.to_owned()),
+                }
+            }
+            names
+        }
+        Some(_) => return Err(StepError::new("parse.columns", "parse –» columns is not an array")),
+    };
+    if has_header && !columns.is_empty() {
+        return Err(StepError::new("parse.columns", "columns name a CSV without a header row, set `\"header\": false`"));
+    }
+
+    let mut types = vec![];
+    for (column, column_type) in string_pairs(parse, "types", "parse.types")? {
+        let column_type = match column_type.as_str() {
+            "string" => ColumnType::String,
+            "int" => ColumnType::Int,
+            "float" => ColumnType::Float,
+            "bool" => ColumnType::Bool,
+            _ => return Err(StepError::new(&format!("parse.types.{}", column), "column types are string, int, float and bool")),
+        };
+        types.push((column, column_type));
+    }
+
+    Ok(CsvParse {
+        input: parse_input(parse)?,
+        delimiter,
+        quote,
+        has_header,
+        columns,
+        types,
+    })
+}
+
+/// One of `body` (a string is sent as it is, anything else as JSON), `form` or `multipart`.
+fn http_body(http: &Value) -> Result<Option<HttpBody>, StepError> {
+    let body = http.get("body").filter(|body| !body.is_null());
+    let form = http.get("form").filter(|form| !form.is_null());
+    let multipart = http.get("multipart").filter(|multipart| !multipart.is_null());
+
+    match (body, form, multipart) {
+        (None, None, None) => Ok(None),
+        (Some(Value::String(body)), None, None) => Ok(Some(HttpBody::Raw(body.to_owned()))),
+        (Some(body), None, None) => Ok(Some(HttpBody::Json(body.clone()))),
+        (None, Some(_), None) => Ok(Some(HttpBody::Form(string_pairs(http, "form", "http.form")?))),
+        (None, None, Some(multipart)) => {
+            let fields = match multipart {
+                Value::Object(fields) => fields,
+                _ => {
+                    return Err(StepError::new("http.multipart", "http –» multipart is not an object"));
+                }
+            };
+            let mut parts = vec![];
+            for (name, field) in fields {
+                if name.contains(['\r', '\n']) {
+                    return Err(StepError::new("http.multipart", "multipart field names can't contain line breaks"));
+                }
+                let part = match field {
+                    Value::Object(_) => MultipartField {
+                        name: name.to_owned(),
+                        value: required_str(field, "content", &format!("http.multipart.{}.content", name))?.to_owned(),
+                        filename: field.get("filename").and_then(|filename| filename.as_str()).map(|filename| filename.to_owned()),
+                        content_type: match field.get("contentType").and_then(|content_type| content_type.as_str()) {
+                            Some(content_type) if content_type.contains(['\r', '\n']) => {
+                                return Err(StepError::new(&format!("http.multipart.{}.contentType", name), "content type can't contain line breaks"));
+                            }
+                            content_type => content_type.map(|content_type| content_type.to_owned()),
+                        },
+                    },
+                    Value::String(value) => MultipartField {
+                        name: name.to_owned(),
+                        value: value.to_owned(),
+                        filename: None,
+                        content_type: None,
+                    },
+                    _ => {
+                        return Err(StepError::new(&format!("http.multipart.{}", name), "multipart field must be a string or a file object"));
                     }
+                };
+                parts.push(part);
+            }
+            Ok(Some(HttpBody::Multipart(parts)))
+        }
+        (_, _, _) => Err(StepError::new("http.body", "only one of body, form and multipart can be set")),
+    }
+}
+
+fn http_expect(http: &Value) -> Result<Option<HttpExpect>, StepError> {
+    let expect = match http.get("expect") {
+        None | Some(Value::Null) => return Ok(None),
+        Some(Value::Object(expect)) => expect,
+        Some(_) => return Err(StepError::new("http.expect", "http –» expect is not an object")),
+    };
+
+    let status_code = |code: &Value| code.as_u64().and_then(|code| u16::try_from(code).ok());
+    let status = match expect.get("status") {
+        None | Some(Value::Null) => Some(vec![]),
+        Some(Value::Array(codes)) => codes.iter().map(status_code).collect(),
+        Some(code) => status_code(code).map(|code| vec![code]),
+    };
+    let status = match status {
+        None => return Err(StepError::new("http.expect.status", "status must be a status code or an array of status codes")),
+        Some(status) => status,
+    };
+
+    let headers = match expect.get("headers") {
+        None | Some(Value::Null) => vec![],
+        Some(Value::Array(names)) => {
+            let mut headers = vec![];
+            for name in names {
+                match name.as_str() {
+                    None => return Err(StepError::new("http.expect.headers", "header names must be strings")),
+                    Some(name) => headers.push((name.to_owned(), None)),
                 }
             }
-            _ => {
-                return Err(serde::de::Error::custom("Unknown step type"));
+            headers
+        }
+        Some(Value::Object(_)) => string_pairs(&Value::Object(expect.clone()), "headers", "http.expect.headers")?
+            .into_iter()
+            .map(|(name, value)| (name, Some(value)))
+            .collect(),
+        Some(_) => return Err(StepError::new("http.expect.headers", "headers must be an array of names or an object")),
+    };
+
+    let body_contains = optional_str(expect.get("bodyContains"), "http.expect.bodyContains")?;
+    let body_matches = match optional_str(expect.get("bodyMatches"), "http.expect.bodyMatches")? {
+        None => None,
+        Some(pattern) => match regex::Regex::new(&pattern) {
+            Ok(regex) => Some(regex),
+            Err(e) => {
+                return Err(StepError::new("http.expect.bodyMatches", format!("invalid regular expression: {}", e)));
             }
+        },
+    };
+
+    let json = match expect.get("json") {
+        None | Some(Value::Null) => vec![],
+        Some(Value::Object(pointers)) => {
+            let mut json = vec![];
+            for (pointer, value) in pointers {
+                if !pointer.is_empty() && !pointer.starts_with('/') {
+                    return Err(StepError::new(&format!("http.expect.json.{}", pointer), "JSON pointers start with `/`"));
+                }
+                json.push((pointer.to_owned(), value.clone()));
+            }
+            json
+        }
+        Some(_) => return Err(StepError::new("http.expect.json", "json must be an object of JSON pointers")),
+    };
+
+    Ok(Some(HttpExpect {
+        status,
+        headers,
+        body_contains,
+        body_matches,
+        json,
+    }))
+}
+
+/// `{"name": {"pointer": "/data/id"}}` or `{"name": {"header": "ETag"}}`.
+fn http_extract(http: &Value) -> Result<Vec<(String, HttpExtract)>, StepError> {
+    let extract = match http.get("extract") {
+        None | Some(Value::Null) => return Ok(vec![]),
+        Some(Value::Object(extract)) => extract,
+        Some(_) => return Err(StepError::new("http.extract", "http –» extract is not an object")),
+    };
+
+    let mut rules = vec![];
+    for (name, rule) in extract {
+        let field = format!("http.extract.{}", name);
+        let rule = match (rule.get("pointer"), rule.get("header")) {
+            (Some(Value::String(pointer)), None) if pointer.is_empty() || pointer.starts_with('/') => HttpExtract::Pointer(pointer.to_owned()),
+            (Some(Value::String(_)), None) => return Err(StepError::new(&field, "JSON pointers start with `/`")),
+            (None, Some(Value::String(header))) => HttpExtract::Header(header.to_owned()),
+            _ => return Err(StepError::new(&field, "extract rules are {\"pointer\": \"/path\"} or {\"header\": \"Name\"}")),
         };
+        rules.push((name.to_owned(), rule));
+    }
+    Ok(rules)
+}
+
+fn http_limits(http: &Value) -> Result<HttpLimits, StepError> {
+    let limits = match http.get("limits") {
+        None | Some(Value::Null) => return Ok(HttpLimits::default()),
+        Some(limits @ Value::Object(_)) => limits,
+        Some(_) => return Err(StepError::new("http.limits", "http –» limits is not an object")),
+    };
 
-        Ok(Step {
-            next,
-            name: name.to_owned(),
-            uuid: uuid.to_owned(),
-            definition,
-            error_on_fail: error_on_fail.to_owned(),
-            created_at: created_at.to_owned(),
-        })
+    let max_redirects = match optional_u64(limits.get("maxRedirects"), "http.limits.maxRedirects")? {
+        None => None,
+        Some(max_redirects) => match u32::try_from(max_redirects) {
+            Err(_) => return Err(StepError::new("http.limits.maxRedirects", "maxRedirects is too large")),
+            Ok(max_redirects) => Some(max_redirects),
+        },
+    };
+    let content_types = match limits.get("contentTypes") {
+        None | Some(Value::Null) => vec![],
+        Some(Value::String(content_type)) => vec![content_type.to_owned()],
+        Some(Value::Array(content_types)) => {
+            let mut types = vec![];
+            for content_type in content_types {
+                match content_type.as_str() {
+                    None => return Err(StepError::new("http.limits.contentTypes", "content types must be strings")),
+                    Some(content_type) => types.push(content_type.to_owned()),
+                }
+            }
+            types
+        }
+        Some(_) => return Err(StepError::new("http.limits.contentTypes", "contentTypes must be a string or an array of strings")),
+    };
+
+    Ok(HttpLimits {
+        connect_timeout_ms: optional_u64(limits.get("connectTimeoutMs"), "http.limits.connectTimeoutMs")?,
+        read_timeout_ms: optional_u64(limits.get("readTimeoutMs"), "http.limits.readTimeoutMs")?,
+        timeout_ms: optional_u64(limits.get("timeoutMs"), "http.limits.timeoutMs")?,
+        max_redirects,
+        max_body_bytes: optional_u64(limits.get("maxBodyBytes"), "http.limits.maxBodyBytes")?,
+        content_types,
+    })
+}
+
+fn rate_limit(http: &Value) -> Result<RateLimit, StepError> {
+    let rate_limit = match http.get("rateLimit") {
+        None | Some(Value::Null) => return Ok(RateLimit::default()),
+        Some(rate_limit @ Value::Object(_)) => rate_limit,
+        Some(_) => return Err(StepError::new("http.rateLimit", "http –» rateLimit is not an object")),
+    };
+
+    let requests_per_second = match rate_limit.get("requestsPerSecond") {
+        None | Some(Value::Null) => None,
+        Some(value) => match value.as_f64() {
+            Some(requests_per_second) if requests_per_second > 0.0 => Some(requests_per_second),
+            _ => return Err(StepError::new("http.rateLimit.requestsPerSecond", "requestsPerSecond must be a number above 0")),
+        },
+    };
+    let max_concurrent = match optional_u64(rate_limit.get("maxConcurrent"), "http.rateLimit.maxConcurrent")? {
+        None => None,
+        Some(max_concurrent) => match u32::try_from(max_concurrent) {
+            Ok(max_concurrent) if max_concurrent > 0 => Some(max_concurrent),
+            _ => return Err(StepError::new("http.rateLimit.maxConcurrent", "maxConcurrent must be between 1 and 4294967295")),
+        },
+    };
+
+    Ok(RateLimit {
+        requests_per_second,
+        max_concurrent,
+    })
+}
+
+/// `"cache": true` or `{"ttlSeconds": 3600}`.
+fn http_cache(http: &Value, http_type: &HttpType) -> Result<Option<HttpCache>, StepError> {
+    let cache = match http.get("cache") {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => return Ok(None),
+        Some(Value::Bool(true)) => HttpCache::default(),
+        Some(cache @ Value::Object(_)) => HttpCache {
+            ttl_seconds: optional_u64(cache.get("ttlSeconds"), "http.cache.ttlSeconds")?,
+        },
+        Some(_) => return Err(StepError::new("http.cache", "cache must be a boolean or an object")),
+    };
+    match http_type {
+        HttpType::Get(_) => Ok(Some(cache)),
+        _ => Err(StepError::new("http.cache", "only GET requests can be cached")),
+    }
+}
+
+fn optional_u64(value: Option<&Value>, field: &str) -> Result<Option<u64>, StepError> {
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => match value.as_u64() {
+            None => Err(StepError::new(field, format!("`{}` is not a positive integer", field))),
+            Some(value) => Ok(Some(value)),
+        },
+    }
+}
+
+/// A string that is a single `{{context.step.path}}` placeholder reads the value as it is, other strings with
+/// placeholders are rendered.
+fn convert_source(source: &Value) -> ConvertSource {
+    if let Value::String(text) = source {
+        let expression = text.trim().strip_prefix("{{").and_then(|rest| rest.strip_suffix("}}"));
+        match expression.map(str::trim).and_then(|expression| expression.strip_prefix("context.")) {
+            Some(path) if !path.contains("{{") && !path.contains("}}") => return ConvertSource::Context(path.to_owned()),
+            _ if text.contains("{{") => return ConvertSource::Template(text.to_owned()),
+            _ => {}
+        }
+    }
+    ConvertSource::Literal(source.clone())
+}
+
+/// A JSON number without a fraction or exponent, of any size.
+fn is_integer(value: &Value) -> bool {
+    let digits = value.to_string();
+    let digits = digits.strip_prefix('-').unwrap_or(&digits);
+    value.is_number() && digits.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+fn convert_type(convert: &Value, key: &str) -> Result<ConvertType, StepError> {
+    let field = format!("convert.{}", key);
+    let name = required_str(convert, key, &field)?;
+    ConvertType::parse(name)
+        .ok_or_else(|| StepError::new(&field, format!("`{}` is not one of string, int, float, bool, decimal, datetime, json, base64 and hex", name)))
+}
+
+/// `fr-FR` and `fr_FR` name the same locale, a bare language like `fr` its main country.
+fn locale(name: &str) -> Option<Locale> {
+    let name = name.replace('-', "_");
+    match name.split_once('_') {
+        Some(_) => name.parse().ok(),
+        None => format!("{}_{}", name, name.to_uppercase()).parse().ok(),
+    }
+}
+
+/// A single character.
+fn separator(convert: &Value, key: &str) -> Result<Option<char>, StepError> {
+    let field = format!("convert.{}", key);
+    match optional_str(convert.get(key), &field)? {
+        None => Ok(None),
+        Some(separator) => {
+            let mut chars = separator.chars();
+            match (chars.next(), chars.next()) {
+                (Some(separator), None) if !separator.is_ascii_digit() => Ok(Some(separator)),
+                _ => Err(StepError::new(&field, format!("{} must be a single character other than a digit", key))),
+            }
+        }
+    }
+}
+
+/// A `chrono` format string, checked for unknown specifiers.
+fn date_format(convert: &Value, key: &str) -> Result<Option<String>, StepError> {
+    let field = format!("convert.{}", key);
+    let format = optional_str(convert.get(key), &field)?;
+    if let Some(format) = &format {
+        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+            return Err(StepError::new(&field, format!("`{}` is not a valid date format", format)));
+        }
+    }
+    Ok(format)
+}
+
+fn optional_str(value: Option<&Value>, field: &str) -> Result<Option<String>, StepError> {
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.to_owned())),
+        Some(_) => Err(StepError::new(field, format!("`{}` is not a string", field))),
+    }
+}
+
+fn required<'a>(json: &'a Value, key: &str, field: &str) -> Result<&'a Value, StepError> {
+    match json.get(key) {
+        None => Err(StepError::new(field, format!("missing field `{}`", field))),
+        Some(value) => Ok(value),
+    }
+}
+
+fn required_str<'a>(json: &'a Value, key: &str, field: &str) -> Result<&'a str, StepError> {
+    match required(json, key, field)?.as_str() {
+        None => Err(StepError::new(field, format!("`{}` is not a string", field))),
+        Some(value) => Ok(value),
     }
 }
 
 pub fn run_parse(steps_str: &str) -> serde_json::Result<Vec<Step>> {
     serde_json::from_str::<Vec<Step>>(steps_str)
 }
+
+/// Same schema as [`run_parse`], written in YAML. The document is either one step or a sequence of steps.
+pub fn run_parse_yaml(steps_str: &str) -> Result<Vec<Step>, serde_yaml::Error> {
+    let value = serde_yaml::from_str::<serde_yaml::Value>(steps_str)?;
+    match value.is_sequence() {
+        true => serde_yaml::from_value::<Vec<Step>>(value),
+        false => serde_yaml::from_value::<Step>(value).map(|step| vec![step]),
+    }
+}
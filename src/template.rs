@@ -0,0 +1,101 @@
+use std::cmp::Reverse;
+use std::env;
+use std::error::Error;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::context::ExecutionContext;
+
+/// Secrets are read from `PSM_SECRET_<NAME>` environment variables.
+const SECRET_ENV_PREFIX: &str = "PSM_SECRET_";
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TemplateError {
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for TemplateError {}
+
+/// Replaces every `{{secrets.NAME}}` and `{{context.step.path}}` placeholder of the template.
+///
+/// `context.step` is the output of an earlier step; the rest of the path walks into objects by key
+/// and into arrays by index. Strings are inserted as they are, other values as JSON.
+pub fn render(template: &str, context: &ExecutionContext) -> Result<String, TemplateError> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            None => {
+                return Err(TemplateError { message: format!("unclosed placeholder in `{}`", template) });
+            }
+            Some(end) => start + end,
+        };
+        rendered.push_str(&rest[..start]);
+        rendered.push_str(&resolve(rest[start + 2..end].trim(), context)?);
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+fn resolve(expression: &str, context: &ExecutionContext) -> Result<String, TemplateError> {
+    match expression.split_once('.') {
+        Some(("secrets", name)) => {
+            env::var(format!("{}{}", SECRET_ENV_PREFIX, name)).map_err(|_| TemplateError {
+                message: format!("secret `{}` is not set, expected {}{}", name, SECRET_ENV_PREFIX, name),
+            })
+        }
+        Some(("context", path)) => match lookup(context, path) {
+            None => Err(TemplateError { message: format!("`{}` is not in the execution context", expression) }),
+            Some(Value::String(value)) => Ok(value.to_owned()),
+            Some(value) => Ok(value.to_string()),
+        },
+        _ => Err(TemplateError {
+            message: format!("unknown placeholder `{}`, expected secrets.NAME or context.step", expression),
+        }),
+    }
+}
+
+/// Puts the `{{secrets.NAME}}` placeholder back wherever the value of a secret appears in `text`, also
+/// JSON-escaped or form-encoded the way bodies carry it.
+pub fn redact_secrets(text: &str) -> String {
+    let mut secrets: Vec<(String, String)> = env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .filter_map(|(key, value)| Some((key.strip_prefix(SECRET_ENV_PREFIX)?.to_owned(), value)))
+        .filter(|(_, value)| !value.is_empty())
+        .collect();
+    // A secret that contains another one is replaced before the shorter one can break it up.
+    secrets.sort_by_key(|(_, value)| Reverse(value.len()));
+
+    let mut redacted = text.to_owned();
+    for (name, value) in secrets {
+        let placeholder = format!("{{{{secrets.{}}}}}", name);
+        let json = Value::String(value.clone()).to_string();
+        let form: String = url::form_urlencoded::byte_serialize(value.as_bytes()).collect();
+        for encoded in [value.as_str(), &json[1..json.len() - 1], &form] {
+            redacted = redacted.replace(encoded, &placeholder);
+        }
+    }
+    redacted
+}
+
+/// Value at `step.path` of the context, e.g. `fetch.body` or `parse_items.0.title`.
+pub fn lookup<'a>(context: &'a ExecutionContext, path: &str) -> Option<&'a Value> {
+    let mut segments = path.split('.');
+    let mut value = context.get(segments.next()?)?;
+    for segment in segments {
+        value = match value {
+            Value::Object(object) => object.get(segment)?,
+            Value::Array(array) => array.get(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::models::Step;
+use crate::serializer;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Diagnostic {
+    pub uuid: Option<String>,
+    pub name: Option<String>,
+    /// Dotted path of the offending field, e.g. `convert.source`.
+    pub field: Option<String>,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl Diagnostic {
+    fn for_step(step: &Step, field: Option<&str>, message: String, severity: Severity) -> Self {
+        Diagnostic {
+            uuid: Some(step.uuid.clone()),
+            name: Some(step.name.clone()),
+            field: field.map(|field| field.to_owned()),
+            message,
+            severity,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: [{}] {}", self.severity, self.uuid.as_deref().unwrap_or("-"), self.name.as_deref().unwrap_or("-"))?;
+        if let Some(field) = &self.field {
+            write!(f, " –» {}", field)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub valid: bool,
+    pub steps: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    pub fn errors(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == Severity::Error).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == Severity::Warning).count()
+    }
+}
+
+/// Deserializes every raw step on its own, so one broken step doesn't hide the others,
+/// then checks the `next` references of the steps that parsed. The first step starts the workflow.
+pub fn validate(values: &[Value]) -> Report {
+    let mut diagnostics = vec![];
+    let mut steps = vec![];
+
+    for value in values {
+        match serializer::parse_step(value) {
+            Ok(step) => steps.push(step),
+            Err(e) => diagnostics.push(Diagnostic {
+                uuid: value.get("uuid").and_then(|uuid| uuid.as_str()).map(|uuid| uuid.to_owned()),
+                name: value.get("name").and_then(|name| name.as_str()).map(|name| name.to_owned()),
+                field: Some(e.field),
+                message: e.message,
+                severity: Severity::Error,
+            }),
+        }
+    }
+
+    // Steps that failed to deserialize still count as existing targets, so their
+    // errors aren't repeated as dangling references.
+    let known: HashSet<&str> = values
+        .iter()
+        .filter_map(|value| value.get("name").and_then(|name| name.as_str()))
+        .collect();
+    let start = values.first().and_then(|value| value.get("name")).and_then(|name| name.as_str());
+    diagnostics.extend(check_graph(&steps, &known, start));
+
+    Report {
+        valid: !diagnostics.iter().any(|d| d.severity == Severity::Error),
+        steps: values.len(),
+        diagnostics,
+    }
+}
+
+/// Graph checks on deserialized steps: duplicates, dangling and self references, cycles and
+/// steps that can't be reached from `start`.
+pub fn check_graph(steps: &[Step], known: &HashSet<&str>, start: Option<&str>) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    let mut names: HashMap<&str, usize> = HashMap::new();
+    let mut uuids: HashSet<&str> = HashSet::new();
+    for step in steps {
+        *names.entry(step.name.as_str()).or_insert(0) += 1;
+        if names[step.name.as_str()] == 2 {
+            diagnostics.push(Diagnostic::for_step(step, Some("name"), format!("duplicate step name `{}`, only the last definition is kept", step.name), Severity::Error));
+        }
+        if !uuids.insert(step.uuid.as_str()) {
+            diagnostics.push(Diagnostic::for_step(step, Some("uuid"), format!("duplicate uuid `{}`", step.uuid), Severity::Warning));
+        }
+    }
+
+    let by_name: HashMap<&str, &Step> = steps.iter().map(|step| (step.name.as_str(), step)).collect();
+
+    for step in steps {
+        let key = step.output_key();
+        if key != step.name && by_name.contains_key(key) {
+            diagnostics.push(Diagnostic::for_step(step, Some("convert.target"), format!("target `{}` replaces the output of the step with that name", key), Severity::Warning));
+        }
+    }
+
+    for step in steps {
+        match next_name(step) {
+            None => {}
+            Some(next) if next == step.name => {
+                diagnostics.push(Diagnostic::for_step(step, Some("next"), "next points to the step itself, the workflow ends here".to_owned(), Severity::Warning));
+            }
+            Some(next) if !known.contains(next) => {
+                diagnostics.push(Diagnostic::for_step(step, Some("next"), format!("next references unknown step `{}`", next), Severity::Error));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let mut reported_cycles: HashSet<&str> = HashSet::new();
+    for step in steps {
+        if let Some(cycle) = find_cycle(step, &by_name) {
+            let first = cycle.iter().min().copied().unwrap_or_default();
+            if reported_cycles.insert(first) {
+                diagnostics.push(Diagnostic::for_step(step, Some("next"), format!("steps form a cycle: {}", cycle.join(" –» ")), Severity::Error));
+            }
+        }
+    }
+
+    if let Some(start) = start.and_then(|start| by_name.get(start).copied()) {
+        let reachable = reachable_from(start, &by_name);
+        for step in steps {
+            if !reachable.contains(step.name.as_str()) {
+                diagnostics.push(Diagnostic::for_step(step, None, format!("step is never reached from the start step `{}`", start.name), Severity::Warning));
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Names of the steps visited when running from `start`.
+pub fn reachable_from<'a>(start: &'a Step, by_name: &HashMap<&str, &'a Step>) -> HashSet<&'a str> {
+    let mut visited = HashSet::new();
+    let mut current = Some(start);
+    while let Some(step) = current {
+        if !visited.insert(step.name.as_str()) {
+            break;
+        }
+        current = next_name(step)
+            .filter(|next| *next != step.name)
+            .and_then(|next| by_name.get(next).copied());
+    }
+    visited
+}
+
+/// `next` of the step, ignoring the empty string DynamoDB items use to end a workflow.
+pub fn next_name(step: &Step) -> Option<&str> {
+    step.next.as_deref().filter(|next| !next.is_empty())
+}
+
+fn find_cycle<'a>(start: &'a Step, by_name: &HashMap<&str, &'a Step>) -> Option<Vec<&'a str>> {
+    let mut path: Vec<&str> = vec![];
+    let mut current = Some(start);
+    while let Some(step) = current {
+        if let Some(position) = path.iter().position(|name| *name == step.name) {
+            let mut cycle = path[position..].to_vec();
+            cycle.push(step.name.as_str());
+            return match position {
+                0 => Some(cycle),
+                // The cycle doesn't include `start`, it is reported when checking one of its own steps.
+                _ => None,
+            };
+        }
+        path.push(step.name.as_str());
+        current = next_name(step)
+            .filter(|next| *next != step.name)
+            .and_then(|next| by_name.get(next).copied());
+    }
+    None
+}
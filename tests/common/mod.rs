@@ -0,0 +1,70 @@
+//! Helpers for the tests against DynamoDB Local. The tests are ignored by default and run with
+//! `DYNAMODB_ENDPOINT=http://localhost:8000 cargo test -- --ignored`.
+
+use std::collections::HashMap;
+use std::env;
+use std::process::Command;
+
+use aws_sdk_dynamodb::model::{AttributeValue, PutRequest, WriteRequest};
+use aws_sdk_dynamodb::{Client, Endpoint};
+
+const ENDPOINT_ENV: &str = "DYNAMODB_ENDPOINT";
+
+/// Client for DynamoDB Local. A missing endpoint fails the test, so `--ignored` never passes without running.
+pub async fn client() -> Client {
+    let endpoint = env::var(ENDPOINT_ENV)
+        .unwrap_or_else(|_| panic!("{} must point at DynamoDB Local to run the ignored tests", ENDPOINT_ENV));
+    // DynamoDB Local accepts any credentials, but the SDK still needs some.
+    for (name, value) in [("AWS_ACCESS_KEY_ID", "local"), ("AWS_SECRET_ACCESS_KEY", "local"), ("AWS_REGION", "us-east-1")] {
+        if env::var(name).is_err() {
+            env::set_var(name, value);
+        }
+    }
+    let config = aws_config::from_env().load().await;
+    let dynamo_config = aws_sdk_dynamodb::config::Builder::from(&config)
+        .endpoint_resolver(Endpoint::immutable(endpoint.parse().expect(ENDPOINT_ENV)))
+        .build();
+    Client::from_conf(dynamo_config)
+}
+
+/// Table name that doesn't clash with other tests or earlier runs.
+pub fn table_name(prefix: &str) -> String {
+    format!("{}-{}", prefix, uuid::Uuid::new_v4().simple())
+}
+
+/// Writes the items in batches, retrying whatever DynamoDB leaves unprocessed.
+pub async fn put_items(client: &Client, table: &str, items: Vec<HashMap<String, AttributeValue>>) {
+    let requests: Vec<WriteRequest> = items
+        .into_iter()
+        .map(|item| WriteRequest::builder().put_request(PutRequest::builder().set_item(Some(item)).build()).build())
+        .collect();
+    for batch in requests.chunks(25) {
+        let mut pending = HashMap::from([(table.to_owned(), batch.to_vec())]);
+        while !pending.is_empty() {
+            let resp = client.batch_write_item().set_request_items(Some(pending)).send().await.expect("batch write");
+            pending = resp.unprocessed_items.unwrap_or_default();
+            pending.retain(|_, requests| !requests.is_empty());
+        }
+    }
+}
+
+/// Text of `count` bytes, so a handful of items exceed the 1 MB page size of `scan` and `query`.
+pub fn padding(count: usize) -> AttributeValue {
+    AttributeValue::S("x".repeat(count))
+}
+
+pub fn s(value: &str) -> AttributeValue {
+    AttributeValue::S(value.to_owned())
+}
+
+/// Runs the `psm` binary against the same endpoint and returns its standard output.
+pub fn psm(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_psm")).args(args).output().expect("psm runs");
+    assert!(
+        output.status.success(),
+        "psm {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("psm prints UTF-8")
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::model::{AttributeDefinition, AttributeValue, BillingMode, KeySchemaElement, KeyType, ScalarAttributeType};
+use aws_sdk_dynamodb::Client;
+use serde_json::Value;
+
+use common::{padding, psm, put_items, s};
+
+/// Steps of one workflow add up to more than 1 MB, so loading them takes several pages.
+const STEPS: usize = 150;
+const OTHER_STEPS: usize = 40;
+const PADDING: usize = 8 * 1024;
+
+async fn create_steps_table(client: &Client, table: &str) {
+    client
+        .create_table()
+        .table_name(table)
+        .attribute_definitions(AttributeDefinition::builder().attribute_name("workflow_id").attribute_type(ScalarAttributeType::S).build())
+        .attribute_definitions(AttributeDefinition::builder().attribute_name("name").attribute_type(ScalarAttributeType::S).build())
+        .key_schema(KeySchemaElement::builder().attribute_name("workflow_id").key_type(KeyType::Hash).build())
+        .key_schema(KeySchemaElement::builder().attribute_name("name").key_type(KeyType::Range).build())
+        .billing_mode(BillingMode::PayPerRequest)
+        .send()
+        .await
+        .expect("create table");
+}
+
+fn step(workflow_id: &str, name: &str) -> HashMap<String, AttributeValue> {
+    HashMap::from([
+        ("workflow_id".to_owned(), s(workflow_id)),
+        ("name".to_owned(), s(name)),
+        ("uuid".to_owned(), s(&uuid::Uuid::new_v4().to_string())),
+        ("type".to_owned(), s("parse")),
+        ("created_at".to_owned(), s("2022-01-30T14:40:46.559Z")),
+        ("error_on_fail".to_owned(), s("Failed to parse the html")),
+        ("next".to_owned(), s("")),
+        ("parse".to_owned(), AttributeValue::M(HashMap::from([
+            ("type".to_owned(), s("document")),
+            ("content".to_owned(), s("<html></html>")),
+        ]))),
+        // Not part of a step, only there to fill the pages.
+        ("padding".to_owned(), padding(PADDING)),
+    ])
+}
+
+/// Two workflows with `STEPS` and `OTHER_STEPS` steps named `<prefix>_<n>`.
+async fn seed(client: &Client, table: &str) {
+    create_steps_table(client, table).await;
+    let mut items: Vec<_> = (0..STEPS).map(|i| step("scraper", &format!("scraper_{:03}", i))).collect();
+    items.extend((0..OTHER_STEPS).map(|i| step("other", &format!("other_{:03}", i))));
+    put_items(client, table, items).await;
+}
+
+/// Number of steps `psm validate` loaded from the table.
+fn validated_steps(args: &[&str]) -> u64 {
+    let report: Value = serde_json::from_str(&psm(args)).expect("JSON report");
+    report["steps"].as_u64().expect("step count")
+}
+
+#[tokio::test]
+#[ignore = "needs DynamoDB Local, run with DYNAMODB_ENDPOINT set and --ignored"]
+async fn scan_steps_reads_every_page() {
+    let client = common::client().await;
+    let table = common::table_name("ParseStep");
+    seed(&client, &table).await;
+
+    let steps = validated_steps(&["validate", "--table", &table, "--format", "json"]);
+
+    client.delete_table().table_name(&table).send().await.expect("delete table");
+    assert_eq!(steps, (STEPS + OTHER_STEPS) as u64);
+}
+
+#[tokio::test]
+#[ignore = "needs DynamoDB Local, run with DYNAMODB_ENDPOINT set and --ignored"]
+async fn query_workflow_returns_only_its_steps() {
+    let client = common::client().await;
+    let table = common::table_name("ParseStep");
+    seed(&client, &table).await;
+
+    let steps = validated_steps(&["validate", "--table", &table, "--workflow", "scraper", "--format", "json"]);
+    let graph = psm(&["graph", "--table", &table, "--workflow", "scraper", "--format", "mermaid"]);
+
+    client.delete_table().table_name(&table).send().await.expect("delete table");
+    assert_eq!(steps, STEPS as u64);
+    for i in 0..STEPS {
+        assert!(graph.contains(&format!("scraper_{:03}", i)), "scraper_{:03} is missing", i);
+    }
+    assert!(!graph.contains("other_"), "steps of another workflow were loaded");
+}
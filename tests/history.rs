@@ -0,0 +1,98 @@
+mod common;
+
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::model::{
+    AttributeDefinition, AttributeValue, BillingMode, GlobalSecondaryIndex, KeySchemaElement, KeyType, Projection,
+    ProjectionType, ScalarAttributeType,
+};
+use aws_sdk_dynamodb::Client;
+use serde_json::Value;
+
+use common::{padding, psm, put_items, s};
+
+/// Events of one execution add up to more than 1 MB, so reading them takes several pages.
+const EVENTS: usize = 200;
+const PADDING: usize = 8 * 1024;
+
+async fn create_history_table(client: &Client, table: &str) {
+    client
+        .create_table()
+        .table_name(table)
+        .attribute_definitions(AttributeDefinition::builder().attribute_name("execution_id").attribute_type(ScalarAttributeType::S).build())
+        .attribute_definitions(AttributeDefinition::builder().attribute_name("sequence").attribute_type(ScalarAttributeType::N).build())
+        .attribute_definitions(AttributeDefinition::builder().attribute_name("workflow_id").attribute_type(ScalarAttributeType::S).build())
+        .key_schema(KeySchemaElement::builder().attribute_name("execution_id").key_type(KeyType::Hash).build())
+        .key_schema(KeySchemaElement::builder().attribute_name("sequence").key_type(KeyType::Range).build())
+        .global_secondary_indexes(
+            GlobalSecondaryIndex::builder()
+                .index_name("workflow_id-index")
+                .key_schema(KeySchemaElement::builder().attribute_name("workflow_id").key_type(KeyType::Hash).build())
+                .projection(Projection::builder().projection_type(ProjectionType::All).build())
+                .build(),
+        )
+        .billing_mode(BillingMode::PayPerRequest)
+        .send()
+        .await
+        .expect("create table");
+}
+
+fn event(execution_id: &str, workflow_id: &str, sequence: usize, event_type: &str, timestamp: &str) -> HashMap<String, AttributeValue> {
+    HashMap::from([
+        ("execution_id".to_owned(), s(execution_id)),
+        ("sequence".to_owned(), AttributeValue::N(sequence.to_string())),
+        ("workflow_id".to_owned(), s(workflow_id)),
+        ("event_type".to_owned(), s(event_type)),
+        ("timestamp".to_owned(), s(timestamp)),
+    ])
+}
+
+/// A long execution of `scraper`, a later short one, and one of another workflow.
+async fn seed(client: &Client, table: &str) {
+    create_history_table(client, table).await;
+    let mut items = vec![event("long", "scraper", 0, "ExecutionStarted", "2022-02-01T10:00:00+00:00")];
+    // Written out of order, the sort key alone has to put them back in sequence.
+    for sequence in (1..EVENTS).rev() {
+        let mut item = event("long", "scraper", sequence, "StepSucceeded", "2022-02-01T10:00:01+00:00");
+        item.insert("step_name".to_owned(), s(&format!("step_{}", sequence)));
+        item.insert("attempt".to_owned(), AttributeValue::N("1".to_owned()));
+        item.insert("output".to_owned(), padding(PADDING));
+        items.push(item);
+    }
+    items.push(event("short", "scraper", 0, "ExecutionStarted", "2022-02-02T10:00:00+00:00"));
+    items.push(event("short", "scraper", 1, "ExecutionSucceeded", "2022-02-02T10:00:01+00:00"));
+    items.push(event("elsewhere", "other", 0, "ExecutionStarted", "2022-02-01T09:00:00+00:00"));
+    put_items(client, table, items).await;
+}
+
+#[tokio::test]
+#[ignore = "needs DynamoDB Local, run with DYNAMODB_ENDPOINT set and --ignored"]
+async fn list_executions_queries_the_workflow_index() {
+    let client = common::client().await;
+    let table = common::table_name("ParseStepHistory");
+    seed(&client, &table).await;
+
+    let executions = psm(&["executions", "scraper", "--history-table", &table]);
+
+    client.delete_table().table_name(&table).send().await.expect("delete table");
+    let ids: Vec<&str> = executions.lines().filter_map(|line| line.split_whitespace().nth(1)).collect();
+    assert_eq!(ids, ["long", "short"]);
+}
+
+#[tokio::test]
+#[ignore = "needs DynamoDB Local, run with DYNAMODB_ENDPOINT set and --ignored"]
+async fn events_reads_every_page_in_sequence_order() {
+    let client = common::client().await;
+    let table = common::table_name("ParseStepHistory");
+    seed(&client, &table).await;
+
+    let events = psm(&["events", "long", "--history-table", &table]);
+
+    client.delete_table().table_name(&table).send().await.expect("delete table");
+    let events: Value = serde_json::from_str(&events).expect("JSON events");
+    let events = events.as_array().expect("array of events");
+    let sequences: Vec<u64> = events.iter().filter_map(|event| event["sequence"].as_u64()).collect();
+    assert_eq!(sequences, (0..EVENTS as u64).collect::<Vec<_>>());
+    assert_eq!(events[0]["eventType"], "ExecutionStarted");
+    assert_eq!(events[1]["stepName"], "step_1");
+}